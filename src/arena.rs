@@ -0,0 +1,335 @@
+use bevy::prelude::*;
+use rand::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::level::{Level, Portal};
+use crate::modes::{GameMode, RunClock};
+use crate::{
+    DeathCause, Food, GameOverEvent, Layer, Position, Size, SnakeSegment, SnakeSegments,
+    ARENA_BORDER, ARENA_HEIGHT, ARENA_WIDTH,
+};
+
+// バトルロイヤルでアリーナが縮むまでのティック数
+pub const SHRINK_TICKS: u32 = 50;
+// アリーナがこれ以上縮まない大きさ
+const MIN_ARENA_SIZE: u32 = 6;
+
+// スネークが動ける範囲（両端を含む）
+#[derive(Clone, Copy)]
+pub struct Arena {
+    pub min_x: u32,
+    pub min_y: u32,
+    pub max_x: u32,
+    pub max_y: u32,
+}
+
+impl Default for Arena {
+    fn default() -> Self {
+        Self::with_border(BorderWidth::default())
+    }
+}
+
+impl Arena {
+    // 外側の枠の壁の内側のアリーナ
+    pub fn with_border(width: BorderWidth) -> Self {
+        let border = width.cells();
+        Self {
+            min_x: border,
+            min_y: border,
+            max_x: ARENA_WIDTH - 1 - border,
+            max_y: ARENA_HEIGHT - 1 - border,
+        }
+    }
+
+    pub fn contains(&self, pos: &Position) -> bool {
+        (self.min_x..=self.max_x).contains(&pos.x) && (self.min_y..=self.max_y).contains(&pos.y)
+    }
+
+    pub fn width(&self) -> u32 {
+        self.max_x - self.min_x + 1
+    }
+
+    pub fn height(&self) -> u32 {
+        self.max_y - self.min_y + 1
+    }
+
+    // 壁から最大marginだけ離れたランダムな位置を返す（アリーナが小さければ中央寄りにする）
+    pub fn random_position(&self, margin: u32) -> Position {
+        self.random_position_where(margin, |_| true).unwrap()
+    }
+
+    // 壁から最大marginだけ離れ、is_freeを満たすマスの中からランダムに選ぶ
+    pub fn random_position_where(
+        &self,
+        margin: u32,
+        is_free: impl Fn(&Position) -> bool,
+    ) -> Option<Position> {
+        let margin_x = margin.min((self.width() - 1) / 2);
+        let margin_y = margin.min((self.height() - 1) / 2);
+        let cells: Vec<Position> = (self.min_y + margin_y..=self.max_y - margin_y)
+            .flat_map(|y| (self.min_x + margin_x..=self.max_x - margin_x).map(move |x| Position { x, y }))
+            .filter(|pos| is_free(pos))
+            .collect();
+        cells.choose(&mut thread_rng()).copied()
+    }
+
+    // 一番外側の1周分のマス
+    fn outer_ring(&self) -> Vec<Position> {
+        let mut ring = Vec::new();
+        for x in self.min_x..=self.max_x {
+            ring.push(Position { x, y: self.min_y });
+            ring.push(Position { x, y: self.max_y });
+        }
+        for y in self.min_y + 1..self.max_y {
+            ring.push(Position { x: self.min_x, y });
+            ring.push(Position { x: self.max_x, y });
+        }
+        ring
+    }
+
+    // 1周分縮める。これ以上縮められなければfalseを返す
+    fn shrink(&mut self) -> bool {
+        if self.width() <= MIN_ARENA_SIZE || self.height() <= MIN_ARENA_SIZE {
+            return false;
+        }
+        self.min_x += 1;
+        self.min_y += 1;
+        self.max_x -= 1;
+        self.max_y -= 1;
+        true
+    }
+}
+
+// 外側の枠の壁の厚さ。盤面の大きさは変わらず、厚いほどスネークが動ける範囲が狭くなる
+#[derive(Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum BorderWidth {
+    #[default]
+    Thin,
+    Medium,
+    Thick,
+}
+
+impl BorderWidth {
+    pub const ALL: [BorderWidth; 3] = [BorderWidth::Thin, BorderWidth::Medium, BorderWidth::Thick];
+
+    // 枠の壁のマス数
+    fn cells(self) -> u32 {
+        match self {
+            BorderWidth::Thin => ARENA_BORDER,
+            BorderWidth::Medium => ARENA_BORDER + 3,
+            BorderWidth::Thick => ARENA_BORDER + 6,
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            BorderWidth::Thin => "thin",
+            BorderWidth::Medium => "medium",
+            BorderWidth::Thick => "thick",
+        }
+    }
+}
+
+// アリーナの端の扱い
+#[derive(Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum WallMode {
+    // 端の壁に当たると死ぬ
+    #[default]
+    Solid,
+    // 端から出ると反対側の端から入ってくる
+    Wrap,
+}
+
+impl WallMode {
+    pub fn label(self) -> &'static str {
+        match self {
+            WallMode::Solid => "solid",
+            WallMode::Wrap => "wrap",
+        }
+    }
+
+    pub fn toggled(self) -> Self {
+        match self {
+            WallMode::Solid => WallMode::Wrap,
+            WallMode::Wrap => WallMode::Solid,
+        }
+    }
+}
+
+// 壁のマス
+#[derive(Component)]
+pub struct Wall;
+
+// アリーナの外側の枠の壁（端を通り抜けるときは隠す）
+#[derive(Component)]
+pub struct Border;
+
+pub fn spawn_wall(commands: &mut Commands, position: Position) {
+    commands
+        .spawn_bundle(SpriteBundle::default())
+        .insert(Wall)
+        .insert(position)
+        .insert(Size::square(1.0));
+}
+
+// アリーナの外側の枠のマスを壁として生成し、死ぬ場所が見えるようにする
+// レベルマップで既に壁になっているマスは除く
+pub fn spawn_border(commands: &mut Commands, level: &Level, arena: &Arena) {
+    for y in 0..ARENA_HEIGHT {
+        for x in 0..ARENA_WIDTH {
+            let pos = Position { x, y };
+            if !arena.contains(&pos) && !level.walls.contains(&pos) {
+                commands
+                    .spawn_bundle(SpriteBundle::default())
+                    .insert(Wall)
+                    .insert(Border)
+                    .insert(pos)
+                    .insert(Size::square(1.0));
+            }
+        }
+    }
+}
+
+// 端を通り抜けるときは外側の枠の壁を隠す
+pub fn show_border(
+    wall_mode: Res<WallMode>,
+    mut borders: Query<(&mut Visibility, ChangeTrackers<Border>)>,
+) {
+    for (mut visibility, tracker) in borders.iter_mut() {
+        if wall_mode.is_changed() || tracker.is_added() {
+            visibility.is_visible = *wall_mode == WallMode::Solid;
+        }
+    }
+}
+
+// 市松模様のグリッドのマス。偶数マスと奇数マスで色を変える
+#[derive(Component)]
+pub struct GridTile {
+    pub odd: bool,
+}
+
+// グリッドを表示するかどうか
+pub struct ShowGrid(pub bool);
+
+impl ShowGrid {
+    // コマンドライン引数に --grid があれば最初から表示する
+    pub fn from_args() -> Self {
+        Self(std::env::args().any(|arg| arg == "--grid"))
+    }
+}
+
+// アリーナの大きさが変わったら（起動時、枠の厚さを変えて始め直したとき、アリーナが縮んだとき）グリッドを作り直す
+pub fn rebuild_grid(
+    mut commands: Commands,
+    arena: Res<Arena>,
+    show_grid: Res<ShowGrid>,
+    tiles: Query<Entity, With<GridTile>>,
+) {
+    if !arena.is_changed() {
+        return;
+    }
+    for tile in tiles.iter() {
+        commands.entity(tile).despawn();
+    }
+    // アリーナの内側の全てのマスに、他のスプライトより奥にグリッドのマスを生成する
+    for y in arena.min_y..=arena.max_y {
+        for x in arena.min_x..=arena.max_x {
+            commands
+                .spawn_bundle(SpriteBundle {
+                    visibility: Visibility {
+                        is_visible: show_grid.0,
+                    },
+                    ..default()
+                })
+                .insert(GridTile { odd: (x + y) % 2 == 1 })
+                .insert(Position { x, y })
+                .insert(Size::square(1.0))
+                .insert(Layer(-1.0));
+        }
+    }
+}
+
+pub fn toggle_grid(
+    keyboard_input: Res<Input<KeyCode>>,
+    mut show_grid: ResMut<ShowGrid>,
+    mut tiles: Query<&mut Visibility, With<GridTile>>,
+) {
+    // Gキーでグリッドの表示を切り替える
+    if keyboard_input.just_pressed(KeyCode::G) {
+        show_grid.0 = !show_grid.0;
+        for mut visibility in tiles.iter_mut() {
+            visibility.is_visible = show_grid.0;
+        }
+    }
+}
+
+// 次にアリーナが縮むまでのティック数
+pub fn ticks_until_shrink(clock: &RunClock) -> u32 {
+    SHRINK_TICKS - clock.ticks % SHRINK_TICKS
+}
+
+pub fn shrink_arena(
+    mut commands: Commands,
+    mode: Res<GameMode>,
+    clock: Res<RunClock>,
+    mut arena: ResMut<Arena>,
+    level: Res<Level>,
+    mut segments: ResMut<SnakeSegments>,
+    mut game_over_writer: EventWriter<GameOverEvent>,
+    segment_positions: Query<&Position, With<SnakeSegment>>,
+    mut food_positions: Query<&mut Position, (With<Food>, Without<SnakeSegment>)>,
+    walls: Query<&Position, (With<Wall>, Without<Food>)>,
+    portals: Query<(Entity, &Position, &Portal), Without<Food>>,
+) {
+    // バトルロイヤルでは一定ティックごとにアリーナの外周を壁に変える
+    if *mode != GameMode::BattleRoyale || ticks_until_shrink(&clock) != SHRINK_TICKS {
+        return;
+    }
+    let ring = arena.outer_ring();
+    if !arena.shrink() {
+        return;
+    }
+    // 外周に掛かったポータルは、壁の中に出ないよう対になるポータルごと消す
+    let covered: Vec<usize> = portals
+        .iter()
+        .filter(|(_, pos, _)| ring.contains(pos))
+        .map(|(_, _, portal)| portal.pair)
+        .collect();
+    for (entity, _, portal) in portals.iter() {
+        if covered.contains(&portal.pair) {
+            commands.entity(entity).despawn();
+        }
+    }
+    // レベルマップで既に壁になっているマスには重ねない
+    let walls: Vec<Position> = walls.iter().copied().collect();
+    for pos in ring {
+        if !walls.contains(&pos) {
+            spawn_wall(&mut commands, pos);
+        }
+    }
+    // 壁に巻き込まれたセグメントから後ろを失い、ヘッドが巻き込まれたらゲームオーバーにする
+    // （このティックで成長したばかりのセグメントはまだ位置を持たないので無視する）
+    let caught = segments
+        .iter()
+        .position(|e| matches!(segment_positions.get(*e), Ok(pos) if !arena.contains(pos)));
+    match caught {
+        Some(0) => game_over_writer.send(GameOverEvent(DeathCause::Wall)),
+        Some(cut) => {
+            for ent in segments.drain(cut..) {
+                commands.entity(ent).despawn();
+            }
+        }
+        None => {}
+    }
+    // 壁に埋まったFoodは、内側のスネークのいないマスに移す
+    let body: Vec<Position> = segment_positions.iter().copied().collect();
+    for mut pos in food_positions.iter_mut() {
+        if !arena.contains(&pos) {
+            if let Some(inside) =
+                arena.random_position_where(0, |pos| !level.blocks(pos) && !body.contains(pos))
+            {
+                *pos = inside;
+            }
+        }
+    }
+}
@@ -0,0 +1,532 @@
+use std::fs::{self, File};
+use std::io::BufWriter;
+use std::path::Path;
+use std::sync::mpsc::{channel, Receiver, Sender, TryRecvError};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use alsa::pcm::{Access, Format, HwParams, PCM};
+use alsa::ValueOr;
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::screen::Screen;
+use crate::settings::Settings;
+use crate::synth::{self, midi, Envelope, Note, Wave};
+use crate::{Direction, FoodSpawnEvent, GameOverEvent, GrowthEvent, SnakeHead, SnakeSegments};
+
+// 出力するサンプリングレート（ALSAが対応していなければ近い値になる）
+const SAMPLE_RATE: u32 = 44100;
+// 1回に書き込むサンプル数（約12ミリ秒）
+const PERIOD: usize = 512;
+// BGMのテンポ（1音の長さ、秒）
+const MUSIC_NOTE: f32 = 0.2;
+// BGMのメロディ（MIDIのノート番号、0は休符）
+const MUSIC: [u8; 16] = [57, 0, 60, 64, 62, 0, 60, 57, 55, 0, 57, 60, 59, 0, 55, 52];
+// スネークがこの長さの倍数になるたびに、食べた音の代わりにレベルアップの音を鳴らす
+pub const LEVEL_UP_LENGTH: usize = 10;
+// 食べた音は長さ2ごとに半音上がり、この半音数で止まる
+const MAX_EAT_PITCH: usize = 24;
+
+// 短くはじく音
+const BLIP: Envelope = Envelope {
+    attack: 0.002,
+    decay: 0.05,
+    sustain: 0.4,
+    release: 0.05,
+};
+// ゆっくり消える音
+const FADE: Envelope = Envelope {
+    attack: 0.005,
+    decay: 0.3,
+    sustain: 0.3,
+    release: 0.3,
+};
+
+// 効果音の種類
+#[derive(Clone, Copy)]
+pub enum Sound {
+    // 食べた後の長さ。長いほど高い音になる
+    Eat(usize),
+    LevelUp,
+    FoodSpawn,
+    Turn,
+    Death,
+}
+
+impl Sound {
+    // 効果音をWAVに書き出すときのファイル名
+    fn name(self) -> String {
+        match self {
+            Sound::Eat(length) => format!("eat-{}", length),
+            Sound::LevelUp => "level-up".to_string(),
+            Sound::FoodSpawn => "food-spawn".to_string(),
+            Sound::Turn => "turn".to_string(),
+            Sound::Death => "death".to_string(),
+        }
+    }
+
+    // シンセサイザーで鳴らす音の組み合わせ
+    fn notes(self) -> Vec<Note> {
+        match self {
+            Sound::Eat(length) => {
+                let pitch = (length / 2).min(MAX_EAT_PITCH) as u8;
+                vec![
+                    Note::new(Wave::Square(0.5), BLIP, 0.0, 0.06, midi(76 + pitch))
+                        .sweep(midi(88 + pitch))
+                        .volume(0.4),
+                ]
+            }
+            Sound::LevelUp => [72, 76, 79, 84]
+                .iter()
+                .enumerate()
+                .map(|(i, &note)| {
+                    Note::new(Wave::Square(0.25), BLIP, i as f32 * 0.07, 0.06, midi(note))
+                        .volume(0.4)
+                })
+                .collect(),
+            Sound::FoodSpawn => {
+                vec![Note::new(Wave::Triangle, BLIP, 0.0, 0.03, midi(93)).volume(0.3)]
+            }
+            Sound::Turn => vec![Note::new(Wave::Noise, BLIP, 0.0, 0.01, 8000.0).volume(0.15)],
+            Sound::Death => vec![
+                Note::new(Wave::Square(0.5), FADE, 0.0, 0.5, midi(69))
+                    .sweep(midi(33))
+                    .volume(0.4),
+                Note::new(Wave::Noise, FADE, 0.0, 0.3, 2000.0)
+                    .sweep(200.0)
+                    .volume(0.3),
+            ],
+        }
+    }
+
+    fn samples(self, rate: u32) -> Vec<f32> {
+        synth::render(&self.notes(), rate)
+    }
+}
+
+// ループするBGMを1周分作る（三角波のメロディと、1小節ごとのノイズのリズム）
+fn music_samples(rate: u32) -> Vec<f32> {
+    let mut notes: Vec<Note> = MUSIC
+        .iter()
+        .enumerate()
+        .filter(|(_, &note)| note != 0)
+        .map(|(i, &note)| {
+            Note::new(
+                Wave::Triangle,
+                BLIP,
+                i as f32 * MUSIC_NOTE,
+                MUSIC_NOTE * 0.6,
+                midi(note),
+            )
+            .volume(0.3)
+        })
+        .collect();
+    notes.extend(
+        (0..MUSIC.len())
+            .step_by(4)
+            .map(|i| Note::new(Wave::Noise, BLIP, i as f32 * MUSIC_NOTE, 0.01, 4000.0).volume(0.1)),
+    );
+    // 1周の長さちょうどで切ってループさせる
+    let mut samples = synth::render(&notes, rate);
+    samples.resize(
+        (MUSIC.len() as f32 * MUSIC_NOTE * rate as f32) as usize,
+        0.0,
+    );
+    samples
+}
+
+// コマンドライン引数の --render-sounds <出力先のディレクトリ> を読む
+pub fn render_sounds_from_args() -> Option<String> {
+    let args: Vec<String> = std::env::args().collect();
+    let i = args.iter().position(|arg| arg == "--render-sounds")?;
+    let dir = args.get(i + 1).cloned();
+    if dir.is_none() {
+        eprintln!("usage: --render-sounds <output-directory>");
+    }
+    dir
+}
+
+// 全ての効果音を鳴らすのと同じ音でWAVに書き出す（食べた音はいくつかの長さで）
+pub fn render_sounds(dir: &str) -> Result<(), String> {
+    fs::create_dir_all(dir).map_err(|err| format!("{}: {}", dir, err))?;
+    let sounds = [
+        Sound::Eat(1),
+        Sound::Eat(LEVEL_UP_LENGTH + 1),
+        Sound::Eat(2 * MAX_EAT_PITCH),
+        Sound::LevelUp,
+        Sound::FoodSpawn,
+        Sound::Turn,
+        Sound::Death,
+    ];
+    let music = music_samples(SAMPLE_RATE);
+    let rendered = sounds
+        .iter()
+        .map(|sound| (sound.name(), sound.samples(SAMPLE_RATE)))
+        .chain(std::iter::once(("music".to_string(), music)));
+    for (name, samples) in rendered {
+        let path = Path::new(dir).join(format!("{}.wav", name));
+        let file = File::create(&path).map_err(|err| format!("{}: {}", path.display(), err))?;
+        synth::write_wav(&mut BufWriter::new(file), &samples, SAMPLE_RATE)
+            .map_err(|err| format!("{}: {}", path.display(), err))?;
+    }
+    println!("Rendered {} sounds to {}", sounds.len() + 1, dir);
+    Ok(())
+}
+
+// 全体、BGM、効果音の音量（0.0〜1.0）
+#[derive(Clone, Copy, Serialize, Deserialize)]
+pub struct Volumes {
+    pub master: f32,
+    pub music: f32,
+    pub sfx: f32,
+}
+
+impl Default for Volumes {
+    fn default() -> Self {
+        Self {
+            master: 0.8,
+            music: 0.5,
+            sfx: 1.0,
+        }
+    }
+}
+
+impl Volumes {
+    // コマンドライン引数の --master-volume, --music-volume, --sfx-volume（0〜100）を読む
+    // 指定がなければ保存した音量にする
+    pub fn from_args(saved: Volumes) -> Self {
+        let args: Vec<String> = std::env::args().collect();
+        let percent = |flag: &str, default: f32| {
+            args.iter()
+                .position(|arg| arg == flag)
+                .and_then(|i| args.get(i + 1))
+                .and_then(|value| value.parse::<f32>().ok())
+                .map(|value| (value / 100.0).clamp(0.0, 1.0))
+                .unwrap_or(default)
+        };
+        Self {
+            master: percent("--master-volume", saved.master),
+            music: percent("--music-volume", saved.music),
+            sfx: percent("--sfx-volume", saved.sfx),
+        }
+    }
+}
+
+// 音を出す先
+trait Backend {
+    fn sample_rate(&self) -> u32;
+    // 書き込めるまで待ってから書き込む
+    fn write(&mut self, samples: &[f32]) -> Result<(), String>;
+}
+
+// ALSAのデフォルトのデバイスにモノラルで出力する
+struct AlsaBackend {
+    pcm: PCM,
+    rate: u32,
+    buffer: Vec<i16>,
+}
+
+impl AlsaBackend {
+    fn open() -> Result<Self, alsa::Error> {
+        let pcm = PCM::new("default", alsa::Direction::Playback, false)?;
+        {
+            let hw = HwParams::any(&pcm)?;
+            hw.set_channels(1)?;
+            hw.set_rate(SAMPLE_RATE, ValueOr::Nearest)?;
+            hw.set_format(Format::s16())?;
+            hw.set_access(Access::RWInterleaved)?;
+            pcm.hw_params(&hw)?;
+        }
+        let rate = pcm.hw_params_current()?.get_rate()?;
+        pcm.prepare()?;
+        Ok(Self {
+            pcm,
+            rate,
+            buffer: Vec::new(),
+        })
+    }
+}
+
+impl Backend for AlsaBackend {
+    fn sample_rate(&self) -> u32 {
+        self.rate
+    }
+
+    fn write(&mut self, samples: &[f32]) -> Result<(), String> {
+        self.buffer.clear();
+        self.buffer.extend(
+            samples
+                .iter()
+                .map(|sample| (sample.clamp(-1.0, 1.0) * i16::MAX as f32) as i16),
+        );
+        let io = self.pcm.io_i16().map_err(|err| err.to_string())?;
+        if let Err(err) = io.writei(&self.buffer) {
+            // 書き込みが間に合わなかったときは、デバイスを立て直して続ける
+            self.pcm
+                .try_recover(err, true)
+                .map_err(|err| err.to_string())?;
+        }
+        Ok(())
+    }
+}
+
+// 何も鳴らさずに、鳴らしたのと同じだけ時間を待つ（サウンドカードのない環境やテスト用）
+struct NullBackend;
+
+impl Backend for NullBackend {
+    fn sample_rate(&self) -> u32 {
+        SAMPLE_RATE
+    }
+
+    fn write(&mut self, samples: &[f32]) -> Result<(), String> {
+        std::thread::sleep(Duration::from_secs_f32(
+            samples.len() as f32 / SAMPLE_RATE as f32,
+        ));
+        Ok(())
+    }
+}
+
+// ゲームからオーディオのスレッドへの指示
+enum AudioCommand {
+    Play(Sound),
+    SetVolumes(Volumes),
+    // BGMを止める（true）か、止めたところから再開する（false）
+    PauseMusic(bool),
+}
+
+// 鳴っている効果音とBGMを混ぜる
+struct Mixer {
+    rate: u32,
+    volumes: Volumes,
+    voices: Vec<(Vec<f32>, usize)>,
+    music: Vec<f32>,
+    music_position: usize,
+    music_paused: bool,
+}
+
+impl Mixer {
+    fn new(rate: u32, volumes: Volumes) -> Self {
+        Self {
+            rate,
+            volumes,
+            voices: Vec::new(),
+            music: music_samples(rate),
+            music_position: 0,
+            music_paused: false,
+        }
+    }
+
+    fn handle(&mut self, command: AudioCommand) {
+        match command {
+            AudioCommand::Play(sound) => self.voices.push((sound.samples(self.rate), 0)),
+            AudioCommand::SetVolumes(volumes) => self.volumes = volumes,
+            AudioCommand::PauseMusic(paused) => self.music_paused = paused,
+        }
+    }
+
+    fn mix(&mut self, out: &mut [f32]) {
+        let Volumes { master, music, sfx } = self.volumes;
+        for sample in out.iter_mut() {
+            let mut value = 0.0;
+            if !self.music_paused && !self.music.is_empty() {
+                value += self.music[self.music_position] * music;
+                self.music_position = (self.music_position + 1) % self.music.len();
+            }
+            for (samples, position) in self.voices.iter_mut() {
+                if let Some(voice) = samples.get(*position) {
+                    value += voice * sfx;
+                    *position += 1;
+                }
+            }
+            *sample = value * master;
+        }
+        self.voices
+            .retain(|(samples, position)| *position < samples.len());
+    }
+}
+
+// オーディオのスレッドに指示を送るリソース
+pub struct AudioOut(Mutex<Sender<AudioCommand>>);
+
+impl AudioOut {
+    pub fn play(&self, sound: Sound) {
+        // スレッドが止まっていたら何もしない
+        self.0.lock().unwrap().send(AudioCommand::Play(sound)).ok();
+    }
+}
+
+// オーディオのスレッドを起動する
+// --no-audio のときやALSAのデバイスが開けないときは、何も鳴らさないバックエンドを使う
+fn start(volumes: Volumes, silent: bool) -> AudioOut {
+    let (sender, receiver) = channel();
+    std::thread::spawn(move || {
+        let mut backend: Box<dyn Backend> = if silent {
+            Box::new(NullBackend)
+        } else {
+            match AlsaBackend::open() {
+                Ok(backend) => Box::new(backend),
+                Err(err) => {
+                    warn!("Failed to open the audio device, playing silently: {}", err);
+                    Box::new(NullBackend)
+                }
+            }
+        };
+        run_mixer(backend.as_mut(), &receiver, volumes);
+    });
+    AudioOut(Mutex::new(sender))
+}
+
+fn run_mixer(backend: &mut dyn Backend, receiver: &Receiver<AudioCommand>, volumes: Volumes) {
+    let mut mixer = Mixer::new(backend.sample_rate(), volumes);
+    let mut buffer = vec![0.0; PERIOD];
+    loop {
+        loop {
+            match receiver.try_recv() {
+                Ok(command) => mixer.handle(command),
+                Err(TryRecvError::Empty) => break,
+                // ゲームが終了した
+                Err(TryRecvError::Disconnected) => return,
+            }
+        }
+        mixer.mix(&mut buffer);
+        if let Err(err) = backend.write(&buffer) {
+            warn!("Audio output failed: {}", err);
+            return;
+        }
+    }
+}
+
+// 音量が変わったらオーディオのスレッドに伝える
+fn update_volumes(volumes: Res<Volumes>, audio: Res<AudioOut>) {
+    if volumes.is_changed() {
+        audio
+            .0
+            .lock()
+            .unwrap()
+            .send(AudioCommand::SetVolumes(*volumes))
+            .ok();
+    }
+}
+
+// ゲームの画面以外（一時停止中、メニュー、ゲームオーバーの画面）ではBGMを止める
+fn pause_music(screen: Res<Screen>, audio: Res<AudioOut>) {
+    if screen.is_changed() {
+        audio
+            .0
+            .lock()
+            .unwrap()
+            .send(AudioCommand::PauseMusic(*screen != Screen::Playing))
+            .ok();
+    }
+}
+
+fn play_sounds(
+    audio: Res<AudioOut>,
+    mut growth_reader: EventReader<GrowthEvent>,
+    mut game_over_reader: EventReader<GameOverEvent>,
+    mut food_spawn_reader: EventReader<FoodSpawnEvent>,
+    segments: Res<SnakeSegments>,
+    heads: Query<&SnakeHead>,
+    mut last_direction: Local<Option<Direction>>,
+) {
+    // ゲームのイベントに合わせて効果音を鳴らす
+    if growth_reader.iter().next().is_some() {
+        let length = segments.len();
+        audio.play(match length % LEVEL_UP_LENGTH {
+            0 => Sound::LevelUp,
+            _ => Sound::Eat(length),
+        });
+    }
+    if game_over_reader.iter().next().is_some() {
+        audio.play(Sound::Death);
+    }
+    if food_spawn_reader.iter().next().is_some() {
+        audio.play(Sound::FoodSpawn);
+    }
+    // ヘッドの向きが変わったら曲がった音を鳴らす
+    let direction = heads.iter().next().map(|head| head.direction);
+    if matches!((*last_direction, direction), (Some(last), Some(now)) if last != now) {
+        audio.play(Sound::Turn);
+    }
+    *last_direction = direction;
+}
+
+// 効果音とBGMを鳴らすSystemとリソースを登録する（ウィンドウ版とターミナル版で共通）
+pub fn add_audio(app: &mut App) -> &mut App {
+    let volumes = Volumes::from_args(app.world.resource::<Settings>().volumes);
+    let silent = std::env::args().any(|arg| arg == "--no-audio");
+    app.insert_resource(volumes)
+        .insert_resource(start(volumes, silent))
+        .add_system(update_volumes)
+        .add_system(pause_music)
+        .add_system_to_stage(CoreStage::PostUpdate, play_sounds)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_sound_renders_until_its_last_note_fades() {
+        let rate = 8000;
+        let sounds = [
+            Sound::Eat(1),
+            Sound::Eat(2 * MAX_EAT_PITCH),
+            Sound::LevelUp,
+            Sound::FoodSpawn,
+            Sound::Turn,
+            Sound::Death,
+        ];
+        for sound in sounds {
+            let end = sound
+                .notes()
+                .iter()
+                .map(|note| note.start + note.length + note.envelope.release)
+                .fold(0.0, f32::max);
+            let samples = sound.samples(rate);
+            assert_eq!(
+                samples.len(),
+                (end * rate as f32).ceil() as usize,
+                "{}",
+                sound.name()
+            );
+            assert!(
+                samples.iter().any(|sample| *sample != 0.0),
+                "{}",
+                sound.name()
+            );
+            // どの効果音もエンベロープの0から始まる
+            assert_eq!(samples[0], 0.0, "{}", sound.name());
+        }
+        // BGMは1周の長さちょうど
+        let music = music_samples(rate);
+        assert_eq!(
+            music.len(),
+            (MUSIC.len() as f32 * MUSIC_NOTE * rate as f32) as usize
+        );
+    }
+
+    #[test]
+    fn music_stops_while_paused_and_resumes_where_it_left_off() {
+        let mut mixer = Mixer::new(8000, Volumes::default());
+        let mut out = vec![0.0; 1000];
+        mixer.mix(&mut out);
+        assert!(out.iter().any(|sample| *sample != 0.0));
+        let position = mixer.music_position;
+
+        mixer.handle(AudioCommand::PauseMusic(true));
+        mixer.mix(&mut out);
+        assert!(out.iter().all(|sample| *sample == 0.0));
+        assert_eq!(mixer.music_position, position);
+
+        // 止めている間も効果音は鳴る
+        mixer.handle(AudioCommand::Play(Sound::Turn));
+        mixer.mix(&mut out);
+        assert!(out.iter().any(|sample| *sample != 0.0));
+
+        mixer.handle(AudioCommand::PauseMusic(false));
+        mixer.mix(&mut out);
+        assert_eq!(mixer.music_position, position + out.len());
+    }
+}
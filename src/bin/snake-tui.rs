@@ -0,0 +1,4 @@
+// ウィンドウもGPUも使わずに、ターミナルで遊ぶ
+fn main() {
+    snake_rust::tui::run();
+}
@@ -0,0 +1,431 @@
+use bevy::ecs::system::SystemParam;
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::gamepad::{self, Controllers};
+use crate::screen::Screen;
+use crate::settings::Settings;
+use crate::theme::Themes;
+use crate::widgets::{self, Focus, ScreenEvents, WidgetButton, WidgetStyle};
+use crate::Direction;
+
+// キーやボタンを割り当てられる操作
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub enum Action {
+    Up,
+    Down,
+    Left,
+    Right,
+    // 今の向きから右に曲がる
+    TurnClockwise,
+    // 今の向きから左に曲がる
+    TurnCounterClockwise,
+    Pause,
+    Restart,
+    // デバッグビルドで、一時停止中に1ティックだけ進める
+    Step,
+}
+
+impl Action {
+    pub const ALL: [Action; 9] = [
+        Action::Up,
+        Action::Down,
+        Action::Left,
+        Action::Right,
+        Action::TurnClockwise,
+        Action::TurnCounterClockwise,
+        Action::Pause,
+        Action::Restart,
+        Action::Step,
+    ];
+
+    fn label(self) -> &'static str {
+        match self {
+            Action::Up => "Up",
+            Action::Down => "Down",
+            Action::Left => "Left",
+            Action::Right => "Right",
+            Action::TurnClockwise => "Turn right",
+            Action::TurnCounterClockwise => "Turn left",
+            Action::Pause => "Pause",
+            Action::Restart => "Restart",
+            Action::Step => "Step (debug)",
+        }
+    }
+}
+
+// 操作に割り当てるキーかゲームパッドのボタン
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum Binding {
+    Key(KeyCode),
+    Button(GamepadButtonType),
+}
+
+impl Binding {
+    fn label(self) -> String {
+        match self {
+            Binding::Key(key) => format!("{:?}", key),
+            Binding::Button(button) => format!("Pad {:?}", button),
+        }
+    }
+
+    // キー同士、ボタン同士なら同じ種類
+    fn same_kind(self, other: Binding) -> bool {
+        matches!(
+            (self, other),
+            (Binding::Key(_), Binding::Key(_)) | (Binding::Button(_), Binding::Button(_))
+        )
+    }
+}
+
+// 操作ごとに割り当てたキーとボタン
+#[derive(Clone, Serialize, Deserialize)]
+pub struct InputMap(Vec<(Action, Vec<Binding>)>);
+
+impl Default for InputMap {
+    fn default() -> Self {
+        Self(
+            Action::ALL
+                .iter()
+                .map(|&action| (action, Self::default_bindings(action)))
+                .collect(),
+        )
+    }
+}
+
+impl InputMap {
+    fn default_bindings(action: Action) -> Vec<Binding> {
+        use Binding::{Button, Key};
+        use GamepadButtonType::*;
+        match action {
+            Action::Up => vec![Key(KeyCode::Up), Button(DPadUp)],
+            Action::Down => vec![Key(KeyCode::Down), Button(DPadDown)],
+            Action::Left => vec![Key(KeyCode::Left), Button(DPadLeft)],
+            Action::Right => vec![Key(KeyCode::Right), Button(DPadRight)],
+            Action::TurnClockwise => vec![Key(KeyCode::D), Button(RightTrigger)],
+            Action::TurnCounterClockwise => vec![Key(KeyCode::A), Button(LeftTrigger)],
+            Action::Pause => vec![Key(KeyCode::P), Button(Start)],
+            Action::Restart => vec![Key(KeyCode::R), Button(Select)],
+            Action::Step => vec![Key(KeyCode::N), Button(North)],
+        }
+    }
+
+    pub fn bindings(&self, action: Action) -> &[Binding] {
+        self.0
+            .iter()
+            .find(|(a, _)| *a == action)
+            .map(|(_, bindings)| bindings.as_slice())
+            .unwrap_or_default()
+    }
+
+    fn bindings_mut(&mut self, action: Action) -> &mut Vec<Binding> {
+        if !self.0.iter().any(|(a, _)| *a == action) {
+            self.0.push((action, Vec::new()));
+        }
+        let index = self.0.iter().position(|(a, _)| *a == action).unwrap();
+        &mut self.0[index].1
+    }
+
+    // 設定ファイルに書かれていない操作には既定の割り当てを使う
+    pub fn with_missing_defaults(mut self) -> Self {
+        for action in Action::ALL {
+            if !self.0.iter().any(|(a, _)| *a == action) {
+                self.0.push((action, Self::default_bindings(action)));
+            }
+        }
+        self
+    }
+
+    // 同じ種類の割り当てを置き換える。他の操作に割り当てられていたら、そちらからは外す
+    pub fn rebind(&mut self, action: Action, binding: Binding) {
+        for (_, bindings) in self.0.iter_mut() {
+            bindings.retain(|b| *b != binding);
+        }
+        let bindings = self.bindings_mut(action);
+        bindings.retain(|b| !b.same_kind(binding));
+        bindings.push(binding);
+    }
+
+    pub fn reset(&mut self, action: Action) {
+        *self.bindings_mut(action) = Self::default_bindings(action);
+    }
+}
+
+// 向きの変え方
+#[derive(Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum ControlScheme {
+    // 上下左右の操作で、その向きに進む
+    #[default]
+    Absolute,
+    // 左右の操作だけを使い、今の向きから左か右に曲がる
+    Relative,
+}
+
+impl ControlScheme {
+    // コマンドライン引数の --relative か --absolute があれば、保存された設定より優先する
+    pub fn from_args(saved: ControlScheme) -> Self {
+        let args: Vec<String> = std::env::args().collect();
+        if args.iter().any(|arg| arg == "--relative") {
+            ControlScheme::Relative
+        } else if args.iter().any(|arg| arg == "--absolute") {
+            ControlScheme::Absolute
+        } else {
+            saved
+        }
+    }
+
+    fn label(self) -> &'static str {
+        match self {
+            ControlScheme::Absolute => "absolute",
+            ControlScheme::Relative => "relative (left and right turn)",
+        }
+    }
+
+    fn toggled(self) -> Self {
+        match self {
+            ControlScheme::Absolute => ControlScheme::Relative,
+            ControlScheme::Relative => ControlScheme::Absolute,
+        }
+    }
+}
+
+// プレイヤーごとに、割り当てに従って操作が押されたかを調べる
+// キーボードは最初のプレイヤーだけが使い、ゲームパッドはControllersの割り当てに従う
+#[derive(SystemParam)]
+pub(crate) struct Actions<'w, 's> {
+    map: Res<'w, InputMap>,
+    keys: Res<'w, Input<KeyCode>>,
+    buttons: Res<'w, Input<GamepadButton>>,
+    axes: Res<'w, Axis<GamepadAxis>>,
+    controllers: Res<'w, Controllers>,
+    #[system_param(ignore)]
+    marker: std::marker::PhantomData<&'s ()>,
+}
+
+impl<'w, 's> Actions<'w, 's> {
+    fn is(
+        &self,
+        action: Action,
+        player: usize,
+        key: impl Fn(KeyCode) -> bool,
+        button: impl Fn(GamepadButton) -> bool,
+    ) -> bool {
+        let gamepad = self.controllers.player(player);
+        self.map
+            .bindings(action)
+            .iter()
+            .any(|&binding| match binding {
+                Binding::Key(code) => player == 0 && key(code),
+                Binding::Button(kind) => {
+                    matches!(gamepad, Some(gamepad) if button(GamepadButton(gamepad, kind)))
+                }
+            })
+    }
+
+    pub fn pressed(&self, action: Action, player: usize) -> bool {
+        self.is(
+            action,
+            player,
+            |key| self.keys.pressed(key),
+            |button| self.buttons.pressed(button),
+        )
+    }
+
+    pub fn just_pressed(&self, action: Action, player: usize) -> bool {
+        self.is(
+            action,
+            player,
+            |key| self.keys.just_pressed(key),
+            |button| self.buttons.just_pressed(button),
+        )
+    }
+
+    // 左スティックが指している向き
+    pub fn stick(&self, player: usize) -> Option<Direction> {
+        self.controllers
+            .player(player)
+            .and_then(|gamepad| gamepad::stick_direction(gamepad, &self.axes))
+    }
+}
+
+// キー割り当ての画面の一覧の枠の高さ（ピクセル）
+const LIST_HEIGHT: f32 = 580.0;
+// 操作の行の後に続く行
+const CONTROLS: usize = Action::ALL.len();
+const BACK: usize = CONTROLS + 1;
+
+// キー割り当ての画面で新しいキーを待っているかどうかと、閉じたときに戻る画面
+#[derive(Default)]
+pub struct BindingsMenu {
+    waiting: bool,
+    back: Screen,
+}
+
+impl BindingsMenu {
+    pub fn returning_to(back: Screen) -> Self {
+        Self { back, ..default() }
+    }
+}
+
+// キー割り当ての画面のUIの一番上のノード
+#[derive(Component)]
+pub struct BindingsRoot;
+
+// ゲーム中にF1でキー割り当ての画面を開く
+pub fn open_bindings(
+    keys: Res<Input<KeyCode>>,
+    mut screen: ResMut<Screen>,
+    mut menu: ResMut<BindingsMenu>,
+) {
+    if *screen == Screen::Playing && keys.just_pressed(KeyCode::F1) {
+        *screen = Screen::Bindings;
+        *menu = BindingsMenu::default();
+    }
+}
+
+// 画面の各行の文字
+fn bindings_labels(
+    map: &InputMap,
+    menu: &BindingsMenu,
+    scheme: ControlScheme,
+    focus: &Focus,
+) -> Vec<String> {
+    let mut labels: Vec<String> = Action::ALL
+        .iter()
+        .enumerate()
+        .map(|(i, action)| {
+            let bindings = if menu.waiting && i == focus.index {
+                "press a key or button...".to_string()
+            } else {
+                let labels: Vec<String> = map.bindings(*action).iter().map(|b| b.label()).collect();
+                labels.join(", ")
+            };
+            format!("{}: {}", action.label(), bindings)
+        })
+        .collect();
+    labels.push(format!("Controls: {}", scheme.label()));
+    labels.push("Back".to_string());
+    labels
+}
+
+// 画面を開いたときにUIを作り、開いている間は割り当ての表示を更新する
+pub fn show_bindings(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    themes: Res<Themes>,
+    screen: Res<Screen>,
+    menu: Res<BindingsMenu>,
+    map: Res<InputMap>,
+    scheme: Res<ControlScheme>,
+    mut focus: ResMut<Focus>,
+    roots: Query<Entity, With<BindingsRoot>>,
+    buttons: Query<(&WidgetButton, &Children)>,
+    mut texts: Query<&mut Text>,
+) {
+    let open = *screen == Screen::Bindings;
+    if screen.is_changed() {
+        for root in roots.iter() {
+            commands.entity(root).despawn_recursive();
+        }
+        if !open {
+            return;
+        }
+        let style = WidgetStyle::from_theme(themes.current(), &asset_server);
+        let labels = bindings_labels(&map, &menu, *scheme, &focus);
+        commands
+            .spawn_bundle(widgets::screen_root(&style))
+            .insert(BindingsRoot)
+            .with_children(|parent| {
+                widgets::title(parent, "KEY BINDINGS", &style);
+                widgets::scrolling_list(parent, LIST_HEIGHT, |parent| {
+                    for (i, label) in labels.iter().enumerate() {
+                        widgets::button(parent, i, label, &style);
+                    }
+                });
+                widgets::label(
+                    parent,
+                    "Enter: change  Delete: reset  Tab: controls  Esc: close",
+                    &style,
+                );
+            });
+        focus.count = labels.len();
+    } else if open && (menu.is_changed() || map.is_changed() || scheme.is_changed()) {
+        let labels = bindings_labels(&map, &menu, *scheme, &focus);
+        widgets::set_button_labels(&labels, &buttons, &mut texts);
+    }
+}
+
+// 上下で操作を選び、Enterで新しいキーかボタンを割り当て、Deleteで既定に戻し、Escか F1で閉じて保存する
+// Tabか「Controls」の行で向きの変え方を切り替える
+pub fn bindings_actions(
+    keys: Res<Input<KeyCode>>,
+    buttons: Res<Input<GamepadButton>>,
+    mut events: ScreenEvents,
+    mut screen: ResMut<Screen>,
+    mut menu: ResMut<BindingsMenu>,
+    mut focus: ResMut<Focus>,
+    mut map: ResMut<InputMap>,
+    mut scheme: ResMut<ControlScheme>,
+    mut settings: ResMut<Settings>,
+) {
+    let input = match events.read(&screen, Screen::Bindings) {
+        Some(input) => input,
+        None => return,
+    };
+    if menu.waiting {
+        // 最初に押されたキーかボタンを割り当てる（Escでやめる）
+        // 待っている間はボタンの数を0にして、押したキーでボタンを操作しないようにしている
+        let action = Action::ALL[focus.index];
+        if let Some(&key) = keys.get_just_pressed().next() {
+            if key != KeyCode::Escape {
+                map.rebind(action, Binding::Key(key));
+            }
+            menu.waiting = false;
+        } else if let Some(GamepadButton(_, button)) = buttons.get_just_pressed().next() {
+            map.rebind(action, Binding::Button(*button));
+            menu.waiting = false;
+        }
+        if !menu.waiting {
+            focus.count = BACK + 1;
+        }
+        return;
+    }
+
+    let tab = keys.just_pressed(KeyCode::Tab)
+        || buttons
+            .get_just_pressed()
+            .any(|GamepadButton(_, pressed)| *pressed == GamepadButtonType::West);
+    let close = input.back || keys.just_pressed(KeyCode::F1);
+    match input.activated {
+        Some(row) if row < CONTROLS => {
+            menu.waiting = true;
+            focus.count = 0;
+        }
+        Some(CONTROLS) => *scheme = scheme.toggled(),
+        Some(BACK) => close_bindings(&mut screen, &menu, &map, *scheme, &mut settings),
+        Some(_) => {}
+        None if close => close_bindings(&mut screen, &menu, &map, *scheme, &mut settings),
+        None if tab => *scheme = scheme.toggled(),
+        None => {
+            if focus.index < CONTROLS
+                && (keys.just_pressed(KeyCode::Delete) || keys.just_pressed(KeyCode::Back))
+            {
+                map.reset(Action::ALL[focus.index]);
+            }
+        }
+    }
+}
+
+// 割り当てを保存して前の画面に戻る
+fn close_bindings(
+    screen: &mut Screen,
+    menu: &BindingsMenu,
+    map: &InputMap,
+    scheme: ControlScheme,
+    settings: &mut Settings,
+) {
+    *screen = menu.back;
+    settings.bindings = map.clone();
+    settings.control_scheme = scheme;
+    settings.save();
+}
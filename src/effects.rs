@@ -0,0 +1,326 @@
+use bevy::prelude::*;
+use rand::prelude::*;
+
+use crate::audio::LEVEL_UP_LENGTH;
+use crate::modes::GameMode;
+use crate::settings::Settings;
+use crate::theme::Themes;
+use crate::{
+    grid_to_world, GameOverEvent, GrowthEvent, Position, Size, SnakeHead, SnakeSegments,
+    ARENA_WIDTH,
+};
+
+// Foodを食べたときに飛び散るパーティクルの数
+const EAT_PARTICLES: usize = 12;
+// 食べたときのパーティクルの速さ（マス/秒）。この1/4〜3/4の速さでばらばらに飛ぶ
+const EAT_SPEED: f32 = 8.0;
+// レベルアップしたときにヘッドから輪になって広がるパーティクルの数と速さ（マス/秒）
+const LEVEL_UP_PARTICLES: usize = 32;
+const LEVEL_UP_SPEED: f32 = 8.0;
+// パーティクルが消えるまでの秒数
+const PARTICLE_LIFETIME: f32 = 0.5;
+// 画面の揺れの長さ（秒）と強さ（マス数）
+const SHAKE_TIME: f32 = 0.3;
+const SHAKE_STRENGTH: f32 = 0.4;
+// 死んだスネークが1セグメントずつ消えていく間隔（秒）と、1セグメントが消えるまでの秒数
+const DISSOLVE_INTERVAL: f32 = 0.04;
+const DISSOLVE_TIME: f32 = 0.3;
+// スコアのポップアップが消えるまでの秒数と、上に流れる速さ（マス/秒）
+const POPUP_TIME: f32 = 0.8;
+const POPUP_RISE: f32 = 2.0;
+
+// 演出ごとのオン・オフ（設定の画面で変えるとすぐに反映する）
+pub struct Effects {
+    pub particles: bool,
+    pub shake: bool,
+    pub dissolve: bool,
+    pub popups: bool,
+}
+
+impl Effects {
+    // 保存した設定を使う。コマンドライン引数に --no-effects があれば、このゲームだけ全ての演出を切る
+    pub fn from_args(settings: &Settings) -> Self {
+        let enabled = !std::env::args().any(|arg| arg == "--no-effects");
+        Self {
+            particles: enabled && settings.particles,
+            shake: enabled && settings.shake,
+            dissolve: enabled && settings.dissolve,
+            popups: enabled && settings.popups,
+        }
+    }
+}
+
+// 1マスの大きさ（ピクセル）
+fn tile_size(window: &Window) -> f32 {
+    window.width() / ARENA_WIDTH as f32
+}
+
+#[derive(Component)]
+pub struct Particle {
+    velocity: Vec2,
+    life: Timer,
+}
+
+#[derive(Component)]
+pub struct ScorePopup(Timer);
+
+// 死んだスネークの残像。delayが終わってからfadeの間に消えていく
+#[derive(Component)]
+pub struct Dissolve {
+    delay: Timer,
+    fade: Timer,
+}
+
+// 画面の揺れの残り時間（秒）
+#[derive(Default)]
+pub struct ScreenShake {
+    remaining: f32,
+}
+
+// centerから飛び散るパーティクル。evenなら等間隔の輪にする
+fn spawn_burst(
+    commands: &mut Commands,
+    center: Vec2,
+    color: Color,
+    count: usize,
+    speed: f32,
+    even: bool,
+    tile: f32,
+) {
+    let mut rng = thread_rng();
+    for i in 0..count {
+        let (angle, speed) = if even {
+            (i as f32 / count as f32 * std::f32::consts::TAU, speed)
+        } else {
+            (
+                rng.gen_range(0.0..std::f32::consts::TAU),
+                rng.gen_range(0.25..0.75) * speed,
+            )
+        };
+        commands
+            .spawn_bundle(SpriteBundle {
+                sprite: Sprite {
+                    color,
+                    custom_size: Some(Vec2::splat(tile * 0.25)),
+                    ..default()
+                },
+                transform: Transform::from_translation(center.extend(1.0)),
+                ..default()
+            })
+            .insert(Particle {
+                velocity: Vec2::new(angle.cos(), angle.sin()) * speed * tile,
+                life: Timer::from_seconds(PARTICLE_LIFETIME, false),
+            });
+    }
+}
+
+// centerの1マス上から上に流れて消える文字
+fn spawn_popup(commands: &mut Commands, text: &str, style: TextStyle, center: Vec2, tile: f32) {
+    commands
+        .spawn_bundle(Text2dBundle {
+            text: Text::with_section(
+                text,
+                style,
+                TextAlignment {
+                    vertical: VerticalAlign::Center,
+                    horizontal: HorizontalAlign::Center,
+                },
+            ),
+            transform: Transform::from_translation((center + Vec2::Y * tile).extend(2.0)),
+            ..default()
+        })
+        .insert(ScorePopup(Timer::from_seconds(POPUP_TIME, false)));
+}
+
+pub fn eat_effects(
+    mut commands: Commands,
+    mut reader: EventReader<GrowthEvent>,
+    effects: Res<Effects>,
+    mode: Res<GameMode>,
+    themes: Res<Themes>,
+    asset_server: Res<AssetServer>,
+    windows: Res<Windows>,
+    segments: Res<SnakeSegments>,
+    heads: Query<&Position, With<SnakeHead>>,
+) {
+    // Foodを食べたら、ヘッドの位置にパーティクルを散らし、スコアのポップアップを出す
+    if reader.iter().next().is_none() {
+        return;
+    }
+    let window = windows.get_primary().unwrap();
+    let tile = tile_size(window);
+    let theme = themes.current();
+    // 効果音と同じく、長さが10の倍数になったらレベルアップの演出にする
+    let level_up = segments.len().is_multiple_of(LEVEL_UP_LENGTH);
+    let text_style = |font_size| TextStyle {
+        font: asset_server.load(theme.font.as_str()),
+        font_size,
+        color: theme.text,
+    };
+    for head in heads.iter() {
+        let center = grid_to_world(head, window);
+        if effects.particles {
+            spawn_burst(
+                &mut commands,
+                center,
+                theme.food,
+                EAT_PARTICLES,
+                EAT_SPEED,
+                false,
+                tile,
+            );
+            if level_up {
+                spawn_burst(
+                    &mut commands,
+                    center,
+                    theme.snake_head,
+                    LEVEL_UP_PARTICLES,
+                    LEVEL_UP_SPEED,
+                    true,
+                    tile,
+                );
+            }
+        }
+        if effects.popups {
+            if level_up {
+                spawn_popup(
+                    &mut commands,
+                    "LEVEL UP",
+                    text_style(tile * 1.5),
+                    center,
+                    tile,
+                );
+            } else if mode.scores_food() {
+                // サバイバルでは食べてもスコアが増えないのでポップアップは出さない
+                spawn_popup(&mut commands, "+1", text_style(tile), center, tile);
+            }
+        }
+    }
+}
+
+pub fn death_effects(
+    mut commands: Commands,
+    mut reader: EventReader<GameOverEvent>,
+    effects: Res<Effects>,
+    themes: Res<Themes>,
+    mut shake: ResMut<ScreenShake>,
+    segments: Res<SnakeSegments>,
+    positions: Query<&Position>,
+) {
+    // 死んだら画面を揺らし、スネークの残像をヘッドから順に消していく
+    // （game_overがスネークを削除する前に、セグメントの並びを読む）
+    if reader.iter().next().is_none() {
+        return;
+    }
+    if effects.shake {
+        shake.remaining = SHAKE_TIME;
+    }
+    if !effects.dissolve {
+        return;
+    }
+    let theme = themes.current();
+    let segment_positions = segments
+        .iter()
+        .filter_map(|e| positions.get(*e).ok())
+        .enumerate();
+    for (i, pos) in segment_positions {
+        let color = if i == 0 {
+            theme.snake_head
+        } else {
+            theme.snake_body
+        };
+        commands
+            .spawn_bundle(SpriteBundle {
+                sprite: Sprite { color, ..default() },
+                ..default()
+            })
+            .insert(*pos)
+            .insert(Size::square(0.8))
+            .insert(Dissolve {
+                delay: Timer::from_seconds(i as f32 * DISSOLVE_INTERVAL, false),
+                fade: Timer::from_seconds(DISSOLVE_TIME, false),
+            });
+    }
+}
+
+pub fn update_particles(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut particles: Query<(Entity, &mut Particle, &mut Transform, &mut Sprite)>,
+) {
+    // パーティクルを飛ばしながら減速させ、だんだん透明にして消す
+    for (entity, mut particle, mut transform, mut sprite) in particles.iter_mut() {
+        particle.life.tick(time.delta());
+        if particle.life.finished() {
+            commands.entity(entity).despawn();
+            continue;
+        }
+        transform.translation += (particle.velocity * time.delta_seconds()).extend(0.0);
+        particle.velocity *= 1.0 - 4.0 * time.delta_seconds();
+        sprite.color.set_a(1.0 - particle.life.percent());
+    }
+}
+
+pub fn update_popups(
+    mut commands: Commands,
+    time: Res<Time>,
+    windows: Res<Windows>,
+    mut popups: Query<(Entity, &mut ScorePopup, &mut Transform, &mut Text)>,
+) {
+    // ポップアップを上に流しながら透明にして消す
+    let tile = tile_size(windows.get_primary().unwrap());
+    for (entity, mut popup, mut transform, mut text) in popups.iter_mut() {
+        popup.0.tick(time.delta());
+        if popup.0.finished() {
+            commands.entity(entity).despawn();
+            continue;
+        }
+        transform.translation.y += POPUP_RISE * tile * time.delta_seconds();
+        for section in text.sections.iter_mut() {
+            section.style.color.set_a(1.0 - popup.0.percent());
+        }
+    }
+}
+
+pub fn update_dissolve(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut ghosts: Query<(Entity, &mut Dissolve, &mut Sprite)>,
+) {
+    // 順番が来た残像から透明にして消す
+    for (entity, mut dissolve, mut sprite) in ghosts.iter_mut() {
+        dissolve.delay.tick(time.delta());
+        if !dissolve.delay.finished() {
+            continue;
+        }
+        dissolve.fade.tick(time.delta());
+        if dissolve.fade.finished() {
+            commands.entity(entity).despawn();
+            continue;
+        }
+        sprite.color.set_a(1.0 - dissolve.fade.percent());
+    }
+}
+
+pub fn shake_camera(
+    time: Res<Time>,
+    windows: Res<Windows>,
+    mut shake: ResMut<ScreenShake>,
+    mut cameras: Query<&mut Transform, (With<Camera>, Without<CameraUi>)>,
+) {
+    // 揺れている間はカメラをランダムにずらし、揺れが収まったら元の位置に戻す
+    let offset = if shake.remaining > 0.0 {
+        shake.remaining -= time.delta_seconds();
+        let strength =
+            SHAKE_STRENGTH * tile_size(windows.get_primary().unwrap()) * shake.remaining.max(0.0)
+                / SHAKE_TIME;
+        let mut rng = thread_rng();
+        Vec2::new(rng.gen_range(-1.0..=1.0), rng.gen_range(-1.0..=1.0)) * strength
+    } else {
+        Vec2::ZERO
+    };
+    for mut transform in cameras.iter_mut() {
+        transform.translation.x = offset.x;
+        transform.translation.y = offset.y;
+    }
+}
@@ -0,0 +1,67 @@
+use bevy::prelude::*;
+
+use crate::Direction;
+
+// 左スティックをこれより倒したときだけ向きを変える
+const STICK_DEADZONE: f32 = 0.5;
+
+// つながっているゲームパッドを、つながった順に並べたもの
+// i番目のゲームパッドがi番目のプレイヤー（スネーク）を操作する。抜けたら後ろのゲームパッドが繰り上がる
+#[derive(Default)]
+pub struct Controllers(Vec<Gamepad>);
+
+impl Controllers {
+    pub fn player(&self, index: usize) -> Option<Gamepad> {
+        self.0.get(index).copied()
+    }
+}
+
+// ゲームの途中でゲームパッドを抜き差ししても、プレイヤーへの割り当てを保つ
+pub fn assign_gamepads(
+    mut events: EventReader<GamepadEvent>,
+    mut controllers: ResMut<Controllers>,
+) {
+    for GamepadEvent(gamepad, event) in events.iter() {
+        match event {
+            GamepadEventType::Connected if !controllers.0.contains(gamepad) => {
+                controllers.0.push(*gamepad);
+                info!(
+                    "Gamepad {} connected as player {}",
+                    gamepad.0,
+                    controllers.0.len()
+                );
+            }
+            GamepadEventType::Disconnected => {
+                controllers.0.retain(|connected| connected != gamepad);
+                info!("Gamepad {} disconnected", gamepad.0);
+            }
+            _ => {}
+        }
+    }
+}
+
+// 左スティックが指している向き
+// 大きく倒している方の軸だけを見て上下左右の4方向にする
+pub fn stick_direction(gamepad: Gamepad, axes: &Axis<GamepadAxis>) -> Option<Direction> {
+    let x = axes
+        .get(GamepadAxis(gamepad, GamepadAxisType::LeftStickX))
+        .unwrap_or(0.0);
+    let y = axes
+        .get(GamepadAxis(gamepad, GamepadAxisType::LeftStickY))
+        .unwrap_or(0.0);
+    if x.abs().max(y.abs()) < STICK_DEADZONE {
+        None
+    } else if x.abs() > y.abs() {
+        Some(if x > 0.0 {
+            Direction::Right
+        } else {
+            Direction::Left
+        })
+    } else {
+        Some(if y > 0.0 {
+            Direction::Up
+        } else {
+            Direction::Down
+        })
+    }
+}
@@ -0,0 +1,123 @@
+use bevy::prelude::*;
+
+use crate::lives::Invulnerable;
+use crate::{DeathCause, GameOverEvent, Position, Size, SnakeHead, SnakeSegment};
+
+// ハザードの移動経路
+#[derive(Clone)]
+pub struct HazardPath {
+    // 1マスずつ辿るマスの並び
+    cells: Vec<Position>,
+    // trueなら最後のマスから最初のマスに戻って周回し、falseなら端で折り返す
+    looping: bool,
+}
+
+impl HazardPath {
+    // 縦か横にまっすぐ並んだ経由点から経路を作る
+    pub fn new(waypoints: &[Position], looping: bool) -> Result<Self, String> {
+        let first = *waypoints.first().ok_or("hazard path needs at least one point")?;
+        let mut legs: Vec<(Position, Position)> =
+            waypoints.windows(2).map(|leg| (leg[0], leg[1])).collect();
+        if looping && waypoints.len() > 1 {
+            legs.push((*waypoints.last().unwrap(), first));
+        }
+        let mut cells = vec![first];
+        for (from, to) in legs {
+            if from.x != to.x && from.y != to.y {
+                return Err(format!(
+                    "hazard path from {},{} to {},{} is not a straight line",
+                    from.x, from.y, to.x, to.y
+                ));
+            }
+            let mut pos = from;
+            while pos != to {
+                pos.x = step_towards(pos.x, to.x);
+                pos.y = step_towards(pos.y, to.y);
+                cells.push(pos);
+            }
+        }
+        // 周回する経路は最初のマスに戻ってくるので、重複を取り除く
+        if looping && cells.len() > 1 {
+            cells.pop();
+        }
+        Ok(Self { cells, looping })
+    }
+}
+
+fn step_towards(from: u32, to: u32) -> u32 {
+    match from.cmp(&to) {
+        std::cmp::Ordering::Less => from + 1,
+        std::cmp::Ordering::Greater => from - 1,
+        std::cmp::Ordering::Equal => from,
+    }
+}
+
+// 決められた経路を移動し、触れたスネークを倒すハザード
+#[derive(Component)]
+pub struct Hazard {
+    path: HazardPath,
+    index: usize,
+    forward: bool,
+}
+
+impl Hazard {
+    fn next_step(&self) -> (usize, bool) {
+        let len = self.path.cells.len();
+        if self.path.looping {
+            ((self.index + 1) % len, true)
+        } else if len == 1 {
+            (0, true)
+        } else if self.forward {
+            if self.index + 1 < len {
+                (self.index + 1, true)
+            } else {
+                (self.index - 1, false)
+            }
+        } else if self.index > 0 {
+            (self.index - 1, false)
+        } else {
+            (1, true)
+        }
+    }
+
+    // 次のステップで移動する先のマス
+    pub fn next_position(&self) -> Position {
+        self.path.cells[self.next_step().0]
+    }
+
+    fn advance(&mut self) -> Position {
+        let (index, forward) = self.next_step();
+        self.index = index;
+        self.forward = forward;
+        self.path.cells[index]
+    }
+}
+
+pub fn spawn_hazard(commands: &mut Commands, path: &HazardPath) {
+    commands
+        .spawn_bundle(SpriteBundle::default())
+        .insert(path.cells[0])
+        .insert(Hazard {
+            path: path.clone(),
+            index: 0,
+            forward: true,
+        })
+        .insert(Size::square(0.8));
+}
+
+pub fn move_hazards(
+    mut game_over_writer: EventWriter<GameOverEvent>,
+    mut hazards: Query<(&mut Position, &mut Hazard), Without<SnakeSegment>>,
+    segments: Query<&Position, With<SnakeSegment>>,
+    invulnerable_heads: Query<&Invulnerable, With<SnakeHead>>,
+) {
+    // ハザードを経路に沿って1マス進め、スネークにぶつかったらゲームオーバーにする
+    let mut hit = false;
+    for (mut pos, mut hazard) in hazards.iter_mut() {
+        *pos = hazard.advance();
+        hit |= segments.iter().any(|segment| *segment == *pos);
+    }
+    if hit && invulnerable_heads.iter().next().is_none() {
+        game_over_writer.send(GameOverEvent(DeathCause::Hazard));
+    }
+}
@@ -0,0 +1,81 @@
+use bevy::prelude::*;
+
+use crate::modes::GameMode;
+use crate::{DeathCause, GameOverEvent, GrowthEvent, SnakeHead, SnakeSegments};
+
+// 満腹時の空腹ゲージ（ティック数）
+const HUNGER_MAX: u32 = 60;
+// HUDに表示するゲージの長さ（文字数）
+const HUNGER_METER_WIDTH: u32 = 10;
+
+// 空腹ゲージを使うかどうか
+pub struct HungerRule(pub bool);
+
+impl HungerRule {
+    // サバイバルモードか、コマンドライン引数に --hunger があれば有効にする
+    pub fn from_args(mode: GameMode) -> Self {
+        Self(mode.uses_hunger() || std::env::args().any(|arg| arg == "--hunger"))
+    }
+}
+
+// スネークの空腹ゲージ。移動するたびに減り、食べると満タンに戻る
+#[derive(Component)]
+pub struct Hunger {
+    pub remaining: u32,
+}
+
+impl Hunger {
+    fn full() -> Self {
+        Self {
+            remaining: HUNGER_MAX,
+        }
+    }
+
+    // HUDに表示するゲージ
+    pub fn meter(&self) -> String {
+        let filled =
+            (self.remaining as f32 / HUNGER_MAX as f32 * HUNGER_METER_WIDTH as f32).ceil() as u32;
+        (0..HUNGER_METER_WIDTH)
+            .map(|i| if i < filled { '█' } else { '░' })
+            .collect()
+    }
+}
+
+pub fn attach_hunger(
+    mut commands: Commands,
+    rule: Res<HungerRule>,
+    heads: Query<Entity, (Added<SnakeHead>, Without<Hunger>)>,
+) {
+    // 新しく生成されたSnakeHeadに空腹ゲージを付ける
+    if !rule.0 {
+        return;
+    }
+    for head in heads.iter() {
+        commands.entity(head).insert(Hunger::full());
+    }
+}
+
+pub fn hunger_tick(
+    mut commands: Commands,
+    mut segments: ResMut<SnakeSegments>,
+    mut growth_reader: EventReader<GrowthEvent>,
+    mut game_over_writer: EventWriter<GameOverEvent>,
+    mut heads: Query<&mut Hunger>,
+) {
+    let ate = growth_reader.iter().next().is_some();
+    for mut hunger in heads.iter_mut() {
+        // 食べたら満タンに戻し、そうでなければゲージを減らす
+        if ate {
+            *hunger = Hunger::full();
+        } else if hunger.remaining > 0 {
+            hunger.remaining -= 1;
+        } else if segments.len() > 1 {
+            // ゲージが空になったら、1ティックごとに尻尾を1つ失う
+            let tail = segments.pop().unwrap();
+            commands.entity(tail).despawn();
+        } else {
+            // 頭だけになったら餓死する
+            game_over_writer.send(GameOverEvent(DeathCause::Starvation));
+        }
+    }
+}
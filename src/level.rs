@@ -0,0 +1,192 @@
+use std::collections::HashMap;
+use std::fs;
+
+use bevy::prelude::*;
+
+use crate::arena::{spawn_border, spawn_wall, Arena};
+use crate::hazard::{spawn_hazard, HazardPath};
+use crate::{Position, Size, ARENA_HEIGHT, ARENA_WIDTH};
+
+// レベルファイルを置くディレクトリ
+const LEVELS_DIR: &str = "assets/levels";
+
+// レベルマップ
+//
+// ARENA_WIDTH x ARENA_HEIGHT の文字のグリッドで、1行目が一番上の行になる。
+// `#` は壁、`.` は床、英数字はポータルで、同じ文字の2マスが対になる。
+// `hazard line 列,行 列,行 ...` の行は経由点を往復するハザード、
+// `hazard loop 列,行 列,行 ...` の行は経由点を周回するハザードになる（列と行はグリッドと同じ数え方）。
+#[derive(Default)]
+pub struct Level {
+    pub walls: Vec<Position>,
+    pub portals: Vec<(Position, Position)>,
+    pub hazards: Vec<HazardPath>,
+}
+
+impl Level {
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut level = Level::default();
+        let mut portal_ends: HashMap<char, Vec<Position>> = HashMap::new();
+        let mut grid_lines = Vec::new();
+        for line in text.lines() {
+            if let Some(hazard) = line.strip_prefix("hazard ") {
+                level.hazards.push(parse_hazard(hazard)?);
+            } else if !line.is_empty() {
+                grid_lines.push(line);
+            }
+        }
+        for (row, line) in grid_lines.into_iter().enumerate() {
+            if row >= ARENA_HEIGHT as usize {
+                return Err(format!("level has more than {} rows", ARENA_HEIGHT));
+            }
+            for (column, tile) in line.chars().enumerate() {
+                if column >= ARENA_WIDTH as usize {
+                    return Err(format!("row {} has more than {} columns", row + 1, ARENA_WIDTH));
+                }
+                let position = grid_position(column as u32, row as u32);
+                match tile {
+                    '.' => {}
+                    '#' => level.walls.push(position),
+                    c if c.is_ascii_alphanumeric() => {
+                        portal_ends.entry(c).or_default().push(position)
+                    }
+                    c => return Err(format!("unknown tile '{}' at row {}", c, row + 1)),
+                }
+            }
+        }
+        let mut names: Vec<char> = portal_ends.keys().copied().collect();
+        names.sort_unstable();
+        for name in names {
+            match portal_ends[&name][..] {
+                [a, b] => level.portals.push((a, b)),
+                _ => return Err(format!("portal '{}' must appear exactly twice", name)),
+            }
+        }
+        Ok(level)
+    }
+
+    // 壁かポータルがあり、スネークやFoodを置けないマスかどうか（ハザードは動くので含まない）
+    pub fn blocks(&self, pos: &Position) -> bool {
+        self.walls.contains(pos) || self.portals.iter().any(|(a, b)| a == pos || b == pos)
+    }
+
+    pub fn load(name: &str) -> Result<Self, String> {
+        let path = format!("{}/{}.txt", LEVELS_DIR, name);
+        let text = fs::read_to_string(&path).map_err(|err| format!("{}: {}", path, err))?;
+        Self::parse(&text).map_err(|err| format!("{}: {}", path, err))
+    }
+}
+
+// ファイルの1行目が一番上（yが最大）になる
+fn grid_position(column: u32, row: u32) -> Position {
+    Position {
+        x: column,
+        y: ARENA_HEIGHT - 1 - row,
+    }
+}
+
+// `line 列,行 列,行 ...` か `loop 列,行 列,行 ...` を読み込む
+fn parse_hazard(text: &str) -> Result<HazardPath, String> {
+    let mut words = text.split_whitespace();
+    let looping = match words.next() {
+        Some("line") => false,
+        Some("loop") => true,
+        other => return Err(format!("unknown hazard kind {:?}", other.unwrap_or(""))),
+    };
+    let waypoints = words
+        .map(|word| {
+            let (column, row) = word
+                .split_once(',')
+                .ok_or_else(|| format!("invalid hazard point '{}'", word))?;
+            let column: u32 = column
+                .parse()
+                .map_err(|_| format!("invalid hazard point '{}'", word))?;
+            let row: u32 = row
+                .parse()
+                .map_err(|_| format!("invalid hazard point '{}'", word))?;
+            if column >= ARENA_WIDTH || row >= ARENA_HEIGHT {
+                return Err(format!("hazard point '{}' is outside the level", word));
+            }
+            Ok(grid_position(column, row))
+        })
+        .collect::<Result<Vec<Position>, String>>()?;
+    HazardPath::new(&waypoints, looping)
+}
+
+// コマンドライン引数の --level で指定されたレベルの名前
+pub fn level_name_from_args() -> Option<String> {
+    let args: Vec<String> = std::env::args().collect();
+    args.iter()
+        .position(|arg| arg == "--level")
+        .and_then(|i| args.get(i + 1))
+        .cloned()
+}
+
+// 名前のレベルを読み込む。名前がなければ何もないアリーナにする
+pub fn load_or_default(name: Option<&str>) -> Level {
+    name.map(|name| {
+        Level::load(name).unwrap_or_else(|err| {
+            warn!("Failed to load level: {}", err);
+            Level::default()
+        })
+    })
+    .unwrap_or_default()
+}
+
+// コマンドライン引数の --level からレベルを読み込む。指定がなければ何もないアリーナにする
+pub fn level_from_args() -> Level {
+    load_or_default(level_name_from_args().as_deref())
+}
+
+// assets/levelsにあるレベルの名前（名前順）
+pub fn level_names() -> Vec<String> {
+    let mut names: Vec<String> = fs::read_dir(LEVELS_DIR)
+        .map(|entries| {
+            entries
+                .filter_map(|entry| entry.ok())
+                .filter_map(|entry| {
+                    let path = entry.path();
+                    if path.extension() == Some("txt".as_ref()) {
+                        path.file_stem().map(|stem| stem.to_string_lossy().into_owned())
+                    } else {
+                        None
+                    }
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+    names.sort();
+    names
+}
+
+// ポータルのマス。ヘッドが入ると対になるポータルから出てくる
+#[derive(Component)]
+pub struct Portal {
+    pub exit: Position,
+    // 何番目の対か（テーマの色を選ぶのに使う）
+    pub pair: usize,
+}
+
+pub fn setup_level(mut commands: Commands, level: Res<Level>, arena: Res<Arena>) {
+    spawn_level(&mut commands, &level, &arena);
+}
+
+// アリーナの枠とレベルの壁とポータルとハザードを生成する
+pub fn spawn_level(commands: &mut Commands, level: &Level, arena: &Arena) {
+    spawn_border(commands, level, arena);
+    for wall in &level.walls {
+        spawn_wall(commands, *wall);
+    }
+    for path in &level.hazards {
+        spawn_hazard(commands, path);
+    }
+    for (pair, (a, b)) in level.portals.iter().enumerate() {
+        for (entrance, exit) in [(*a, *b), (*b, *a)] {
+            commands
+                .spawn_bundle(SpriteBundle::default())
+                .insert(Portal { exit, pair })
+                .insert(entrance)
+                .insert(Size::square(0.9));
+        }
+    }
+}
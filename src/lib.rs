@@ -0,0 +1,979 @@
+// Bevyのシステムは引数やクエリの型が多くなりがちなので許可する
+#![allow(clippy::too_many_arguments, clippy::type_complexity)]
+
+use std::collections::HashMap;
+
+// ゲームの固定タイムステップを定義
+use bevy::core::FixedTimestep;
+// Bevyの前準備
+use bevy::prelude::*;
+// ランダムな数値を生成
+use rand::prelude::*;
+use serde::{Deserialize, Serialize};
+
+mod arena;
+mod audio;
+mod bindings;
+mod effects;
+mod gamepad;
+mod hazard;
+mod hunger;
+mod level;
+mod lives;
+mod menu;
+mod modes;
+mod pause;
+mod pointer;
+mod raster;
+mod replay;
+mod screen;
+mod settings;
+mod snapshot;
+mod sprites;
+mod summary;
+mod synth;
+#[cfg(test)]
+mod tests;
+mod theme;
+mod tick;
+pub mod tui;
+mod widgets;
+use arena::{Arena, BorderWidth, ShowGrid, Wall, WallMode};
+use bindings::{Action, Actions, ControlScheme};
+use gamepad::Controllers;
+use hazard::Hazard;
+use hunger::{Hunger, HungerRule};
+use level::{Level, Portal};
+use lives::{Invulnerable, Lives};
+use modes::{GameMode, HighScores, RunClock, RunOverEvent, StartRunEvent};
+use screen::Screen;
+use settings::Settings;
+use snapshot::StartingBoard;
+use sprites::SnakeSpriteSheet;
+use theme::Themes;
+
+// スネークの速度を定義
+const SNAKE_SPEED: u32 = 1;
+// ハザードが1マス進む間隔（秒）
+const HAZARD_STEP: f64 = 0.25;
+// 残機の初期値
+pub(crate) const SNAKE_LIVES: u32 = 3;
+
+// アリーナの境界線とサイズを定義
+const ARENA_BORDER: u32 = 1;
+const ARENA_HEIGHT: u32 = 30; // 画面の高さ / アリーナの高さ
+const ARENA_WIDTH: u32 = 30; // 画面の幅 / アリーナの幅
+// リスポーン時に壁から確保する距離
+const SPAWN_MARGIN: u32 = 5;
+
+// ゲームオブジェクトの構造体とそれに付随するコンポーネントの定義
+#[derive(Component, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+struct Position {
+    x: u32,
+    y: u32,
+}
+
+impl Position {
+    // 指定した方向に1マス進んだ位置
+    // 0から左や下に進むと、アンダーフローせずに盤面の外（u32の最大値の側）に出る
+    fn step(self, direction: Direction) -> Self {
+        let mut pos = self;
+        match direction {
+            Direction::Left => {
+                pos.x = pos.x.wrapping_sub(SNAKE_SPEED);
+            }
+            Direction::Right => {
+                pos.x += SNAKE_SPEED;
+            }
+            Direction::Up => {
+                pos.y += SNAKE_SPEED;
+            }
+            Direction::Down => {
+                pos.y = pos.y.wrapping_sub(SNAKE_SPEED);
+            }
+        };
+        pos
+    }
+}
+
+// 描画の前後関係（大きいほど手前に描画する。なければ0）
+#[derive(Component)]
+struct Layer(f32);
+
+#[derive(Component)]
+struct Size {
+    width: f32,
+    height: f32,
+}
+impl Size {
+    pub fn square(x: f32) -> Self {
+        Self {
+            width: x,
+            height: x,
+        }
+    }
+}
+
+#[derive(Component)]
+struct SnakeHead {
+    direction: Direction,
+}
+// ゲームオーバー時にトリガーするイベント。死んだ原因を持つ
+struct GameOverEvent(DeathCause);
+
+// スネークが死んだ原因
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum DeathCause {
+    // アリーナの端や壁に当たった（バトルロイヤルで縮んだアリーナに巻き込まれた場合も含む）
+    Wall,
+    // 自分の体に当たった
+    Itself,
+    Hazard,
+    Starvation,
+}
+
+impl DeathCause {
+    fn label(self) -> &'static str {
+        match self {
+            DeathCause::Wall => "Hit a wall",
+            DeathCause::Itself => "Ran into itself",
+            DeathCause::Hazard => "Hit a hazard",
+            DeathCause::Starvation => "Starved",
+        }
+    }
+}
+// スネークが成長したときにトリガーするイベント
+struct GrowthEvent;
+
+#[derive(Default)]
+struct FoodSpawnEvent;
+
+#[derive(Default)]
+// 最終的なTailの位置を保持
+struct LastTailPosition(Option<Position>);
+
+#[derive(Component)]
+struct SnakeSegment;
+
+#[derive(Default, Deref, DerefMut)]
+struct SnakeSegments(Vec<Entity>);
+
+#[derive(Component)]
+struct Food;
+
+// スコア表示用のテキストを識別するマーカー
+#[derive(Component)]
+struct ScoreText;
+
+#[derive(PartialEq, Copy, Clone, Default, Debug, Serialize, Deserialize)]
+enum Direction {
+    Left,
+    #[default]
+    Up,
+    Right,
+    Down,
+}
+// 方向を逆転させる
+impl Direction {
+    const ALL: [Direction; 4] = [
+        Direction::Left,
+        Direction::Up,
+        Direction::Right,
+        Direction::Down,
+    ];
+
+
+    fn opposite(self) -> Self {
+        match self {
+            Self::Left => Self::Right,
+            Self::Right => Self::Left,
+            Self::Up => Self::Down,
+            Self::Down => Self::Up,
+        }
+    }
+
+    // 右に90度曲がった向き
+    fn clockwise(self) -> Self {
+        match self {
+            Self::Up => Self::Right,
+            Self::Right => Self::Down,
+            Self::Down => Self::Left,
+            Self::Left => Self::Up,
+        }
+    }
+
+    // 左に90度曲がった向き
+    fn counter_clockwise(self) -> Self {
+        self.clockwise().opposite()
+    }
+}
+// カメラを作成するための関数
+fn setup_camera(mut commands: Commands, asset_server: Res<AssetServer>, themes: Res<Themes>) {
+    // カメラを2Dに設定
+    commands.spawn_bundle(OrthographicCameraBundle::new_2d());
+    // メニューのUIを表示するカメラ
+    commands.spawn_bundle(UiCameraBundle::default());
+    // スコアと残機を表示するテキストを作成（内容はscoreboardで更新する）
+    commands.spawn_bundle(Text2dBundle {
+        text: Text::with_section(
+            "",
+            TextStyle {
+                font: asset_server.load(themes.current().font.as_str()),
+                font_size: 40.0,
+                color: themes.current().text,
+            },
+            TextAlignment {
+                vertical: VerticalAlign::Center,
+                horizontal: HorizontalAlign::Center,
+            },
+        ),
+        transform: Transform::from_translation(Vec3::new(0.0, 400.0, 0.0)),
+        ..Default::default()
+    })
+    .insert(ScoreText);
+}
+
+fn scoreboard(
+    mode: Res<GameMode>,
+    score: Res<u32>,
+    lives: Res<Lives>,
+    segments: Res<SnakeSegments>,
+    clock: Res<RunClock>,
+    high_scores: Res<HighScores>,
+    hungers: Query<&Hunger>,
+    mut query: Query<&mut Text, With<ScoreText>>,
+) {
+    // モードに応じてスコア、残機、残り時間、空腹ゲージなどを表示する
+    for mut text in query.iter_mut() {
+        text.sections[0].value = modes::hud_text(
+            *mode,
+            *score,
+            lives.remaining,
+            segments.len(),
+            hungers.iter().next(),
+            &clock,
+            &high_scores,
+        );
+    }
+}
+
+fn setup_snake(
+    mut commands: Commands,
+    mut segments: ResMut<SnakeSegments>,
+    mut score: ResMut<u32>,
+    arena: Res<Arena>,
+    level: Res<Level>,
+    board: Res<StartingBoard>,
+) {
+    match &board.0 {
+        // --board があれば、その盤面のスネークとFoodとスコアから始める
+        Some(board) => {
+            snapshot::spawn_board(&mut commands, &mut segments, board);
+            *score = board.score;
+        }
+        None => {
+            spawn_snake(&mut commands, &mut segments, &arena, &level);
+        }
+    }
+}
+
+fn spawn_snake(
+    commands: &mut Commands,
+    segments: &mut SnakeSegments,
+    arena: &Arena,
+    level: &Level,
+) -> Entity {
+    // ヘッドの初期座標を壁から離れた、壁やポータルのない位置にランダムに決定
+    let position = arena
+        .random_position_where(SPAWN_MARGIN, |pos| !level.blocks(pos))
+        .unwrap_or_else(|| arena.random_position(SPAWN_MARGIN));
+    // 方向を、すぐに壁にぶつからない方向からランダムに決定
+    let directions: Vec<Direction> = Direction::ALL
+        .into_iter()
+        .filter(|dir| {
+            let next = position.step(*dir);
+            arena.contains(&next) && !level.blocks(&next)
+        })
+        .collect();
+    let direction = directions
+        .choose(&mut thread_rng())
+        .copied()
+        .unwrap_or(Direction::Up);
+
+    // スネークのヘッドを生成し、初期位置と方向を設定（色はテーマで塗る）
+    let head = commands
+        .spawn_bundle(SpriteBundle {
+            transform: Transform::from_translation(Vec3::new(0.0, 0.0, 0.0)),
+            ..Default::default()
+        })
+        .insert(SnakeHead { direction })
+        .insert(SnakeSegment)
+        .insert(position)
+        .insert(Size::square(0.8))
+        .id();
+    *segments = SnakeSegments(vec![head]);
+    head
+}
+
+fn spawn_segment(commands: &mut Commands, position: Position) -> Entity {
+    // スネークのセグメントを生成し、位置を設定
+    commands
+        .spawn_bundle(SpriteBundle::default())
+        .insert(SnakeSegment)
+        .insert(position)
+        .insert(Size::square(0.8))
+        .id()
+}
+
+fn snake_movement(
+    mut last_tail_position: ResMut<LastTailPosition>,
+    mut game_over_writer: EventWriter<GameOverEvent>,
+    segments: ResMut<SnakeSegments>,
+    arena: Res<Arena>,
+    wall_mode: Res<WallMode>,
+    mut heads: Query<(Entity, &SnakeHead, Option<&Invulnerable>)>,
+    mut positions: Query<&mut Position, With<SnakeSegment>>,
+    obstacles: Query<
+        (&Position, Option<&Hazard>),
+        (Or<(With<Wall>, With<Hazard>)>, Without<SnakeSegment>),
+    >,
+    portals: Query<(&Position, &Portal), Without<SnakeSegment>>,
+) {
+    // スネークのヘッドとボディを移動させる
+    if let Some((head_entity, head, invulnerable)) = heads.iter_mut().next() {
+        let segment_positions = segments
+            .iter()
+            .map(|e| *positions.get_mut(*e).unwrap())
+            .collect::<Vec<Position>>();
+        let mut head_pos = positions.get_mut(head_entity).unwrap();
+        let previous_head_pos = *head_pos;
+        *head_pos = head_pos.step(head.direction);
+        // ポータルに入ったら、同じ方向のまま対になるポータルの1マス先に出る
+        // （ボディはヘッドが通ったマスを順に辿るので、そのままポータルを通って付いていく）
+        if let Some((_, portal)) = portals.iter().find(|(pos, _)| **pos == *head_pos) {
+            *head_pos = portal.exit.step(head.direction);
+        }
+        // 端を通り抜けるときは、アリーナの外に出たら反対側の端に移る
+        if *wall_mode == WallMode::Wrap && !arena.contains(&head_pos) {
+            match head.direction {
+                Direction::Left => head_pos.x = arena.max_x,
+                Direction::Right => head_pos.x = arena.min_x,
+                Direction::Up => head_pos.y = arena.min_y,
+                Direction::Down => head_pos.y = arena.max_y,
+            }
+        }
+        // スネークがアリーナの外に出たか壁やハザードに当たったかを判定し、その場合はゲームオーバーにする
+        let obstacle = obstacles.iter().find(|(pos, _)| **pos == *head_pos);
+        if !arena.contains(&head_pos) || obstacle.is_some() {
+            // 無敵中は壁の手前で止まる
+            if invulnerable.is_some() {
+                *head_pos = previous_head_pos;
+                return;
+            }
+            game_over_writer.send(GameOverEvent(match obstacle {
+                Some((_, Some(_))) => DeathCause::Hazard,
+                _ => DeathCause::Wall,
+            }));
+        }
+        // スネークが自分自身に当たった場合はゲームオーバーにする（無敵中は通り抜ける）
+        if invulnerable.is_none() && segment_positions.contains(&head_pos) {
+            game_over_writer.send(GameOverEvent(DeathCause::Itself));
+        }
+        // スネークのボディを移動
+        segment_positions
+            .iter()
+            .zip(segments.iter().skip(1))
+            .for_each(|(pos, segment)| {
+                *positions.get_mut(*segment).unwrap() = *pos;
+            });
+        *last_tail_position = LastTailPosition(Some(*segment_positions.last().unwrap()));
+    }
+}
+
+fn snake_movement_input(
+    actions: Actions,
+    scheme: Res<ControlScheme>,
+    clock: Res<RunClock>,
+    mut last_turns: ResMut<LastTurns>,
+    mut heads: Query<&mut SnakeHead>,
+) {
+    // SnakeHeadごとに、割り当てられたキーとゲームパッドの入力で方向を変更する（キーボードは最初のスネークだけ）
+    for (player, mut head) in heads.iter_mut().enumerate() {
+        let pressed = |action| actions.just_pressed(action, player);
+        // 今の向きから曲がる操作。相対操作では左右の操作も曲がる操作になる
+        let relative = *scheme == ControlScheme::Relative;
+        let dir = if pressed(Action::TurnClockwise) || (relative && pressed(Action::Right)) {
+            Some(head.direction.clockwise())
+        } else if pressed(Action::TurnCounterClockwise) || (relative && pressed(Action::Left)) {
+            Some(head.direction.counter_clockwise())
+        } else if relative {
+            None
+        } else {
+            [
+                (Action::Left, Direction::Left),
+                (Action::Down, Direction::Down),
+                (Action::Up, Direction::Up),
+                (Action::Right, Direction::Right),
+            ]
+            .into_iter()
+            .find(|(action, _)| actions.pressed(*action, player))
+            .map(|(_, dir)| dir)
+            .or_else(|| actions.stick(player))
+        };
+        if let Some(dir) = dir {
+            try_turn(&mut head, dir, player, &clock, &mut last_turns);
+        }
+    }
+}
+
+// プレイヤーごとに、最後に曲がったティック
+#[derive(Default)]
+struct LastTurns(HashMap<usize, u32>);
+
+// SnakeHeadの向きを変える。反対の方向には曲がらず、曲がるのは1ティックに1回だけにする
+// （1ティックに2回曲がると、動く前に真後ろを向いてしまう）
+fn try_turn(
+    head: &mut SnakeHead,
+    dir: Direction,
+    player: usize,
+    clock: &RunClock,
+    last_turns: &mut LastTurns,
+) {
+    if dir == head.direction || dir == head.direction.opposite() {
+        return;
+    }
+    if last_turns.0.get(&player) == Some(&clock.ticks) {
+        return;
+    }
+    last_turns.0.insert(player, clock.ticks);
+    head.direction = dir;
+}
+
+// 割り当てたキーでプレイをやり直す（一時停止のメニューのRestartと同じく、途中のプレイは記録しない）
+fn restart_on_action(actions: Actions, mut start_writer: EventWriter<StartRunEvent>) {
+    if actions.just_pressed(Action::Restart, 0) {
+        start_writer.send(StartRunEvent);
+    }
+}
+
+fn game_over(
+    mut commands: Commands,
+    // ゲームオーバーイベントを受け取る
+    mut reader: EventReader<GameOverEvent>,
+    mut run_over_writer: EventWriter<RunOverEvent>,
+    mut lives: ResMut<Lives>,
+    mut segments_res: ResMut<SnakeSegments>,
+    mut recent: ResMut<summary::RecentBoards>,
+    arena: Res<Arena>,
+    level: Res<Level>,
+    segments: Query<Entity, With<SnakeSegment>>,
+) {
+    if reader.iter().next().is_none() {
+        return;
+    }
+    lives.remaining = lives.remaining.saturating_sub(1);
+    if lives.remaining > 0 {
+        // 残機があれば、死んだスネークを削除し、スコアを保ったまま無敵状態でリスポーンする
+        info!("Lost a life! Lives left: {}", lives.remaining);
+        for ent in segments.iter() {
+            commands.entity(ent).despawn();
+        }
+        let head = spawn_snake(&mut commands, &mut segments_res, &arena, &level);
+        commands.entity(head).insert(Invulnerable::new());
+        // ミニリプレイに死ぬ前のスネークが混ざらないようにする
+        recent.clear();
+    } else {
+        // 残機がなくなれば、このプレイを終了する
+        run_over_writer.send(RunOverEvent);
+    }
+}
+
+fn restart_run(
+    mut commands: Commands,
+    mut reader: EventReader<RunOverEvent>,
+    mut start_reader: EventReader<StartRunEvent>,
+    mut lives: ResMut<Lives>,
+    mut score: ResMut<u32>,
+    mut clock: ResMut<RunClock>,
+    mut arena: ResMut<Arena>,
+    mut segments_res: ResMut<SnakeSegments>,
+    level: Res<Level>,
+    border_width: Res<BorderWidth>,
+    mut stats: ResMut<summary::RunStats>,
+    mut last_turns: ResMut<LastTurns>,
+    mut recent: ResMut<summary::RecentBoards>,
+    food: Query<Entity, With<Food>>,
+    segments: Query<Entity, With<SnakeSegment>>,
+    tiles: Query<Entity, Or<(With<Wall>, With<Portal>, With<Hazard>)>>,
+) {
+    // プレイが終了したかメニューから始めたら、全てのFood、SnakeSegment、壁を削除し、スコアと残機とアリーナとレベルをリセットして新しいSnakeを生成する
+    let run_over = reader.iter().next().is_some();
+    let started = start_reader.iter().next().is_some();
+    if !run_over && !started {
+        return;
+    }
+    if run_over {
+        info!("Game Over! Score: {}", *score);
+    }
+    for ent in food.iter().chain(segments.iter()).chain(tiles.iter()) {
+        commands.entity(ent).despawn();
+    }
+    *score = 0;
+    lives.reset();
+    *clock = RunClock::default();
+    *stats = summary::RunStats::default();
+    last_turns.0.clear();
+    recent.clear();
+    *arena = Arena::with_border(*border_width);
+    level::spawn_level(&mut commands, &level, &arena);
+    spawn_snake(&mut commands, &mut segments_res, &arena, &level);
+}
+
+fn snake_eating(
+    mut commands: Commands,
+    mut growth_writer: EventWriter<GrowthEvent>,
+    mode: Res<GameMode>,
+    mut score: ResMut<u32>,
+    food_positions: Query<(Entity, &Position), With<Food>>,
+    head_positions: Query<&Position, With<SnakeHead>>,
+) {
+    // SnakeHeadがFoodに接触したら、Foodを削除し、Snakeを成長させ、スコアを更新する
+    for head_pos in head_positions.iter() {
+        for (ent, food_pos) in food_positions.iter() {
+            if food_pos == head_pos {
+                commands.entity(ent).despawn();
+                growth_writer.send(GrowthEvent);
+                // サバイバルでは生き延びた時間がスコアになるので、食べてもスコアは増えない
+                if mode.scores_food() {
+                    *score += 1;
+                    info!("Score: {}", *score);
+                }
+            }
+        }
+    }
+}
+
+fn snake_growth(
+    mut commands: Commands,
+    last_tail_position: Res<LastTailPosition>,
+    mut segments: ResMut<SnakeSegments>,
+    mut growth_reader: EventReader<GrowthEvent>,
+) {
+    // 成長イベントがあれば、Snakeを成長させる
+    if growth_reader.iter().next().is_some() {
+        segments.push(spawn_segment(&mut commands, last_tail_position.0.unwrap()));
+    }
+}
+
+fn size_scaling(windows: Res<Windows>, mut q: Query<(&Size, &mut Transform)>) {
+    let window = windows.get_primary().unwrap();
+    for (sprite_size, mut transform) in q.iter_mut() {
+        // ウィンドウサイズに合わせてスプライトサイズを変更する
+        transform.scale = Vec3::new(
+            sprite_size.width / ARENA_WIDTH as f32 * window.width() as f32,
+            sprite_size.height / ARENA_HEIGHT as f32 * window.height() as f32,
+            1.0,
+        );
+    }
+}
+
+// ゲーム内の位置をウィンドウ上の座標に変換する
+fn grid_to_world(pos: &Position, window: &Window) -> Vec2 {
+    // ゲーム内の位置とウィンドウサイズの範囲を変換する関数
+    fn convert(pos: f32, bound_window: f32, bound_game: f32) -> f32 {
+        let tile_size = bound_window / (bound_game / 2.0); // 画面の半分を使うために2で割る
+        // ウィンドウサイズを合わせて中央に揃える
+        pos / bound_game * bound_window - (bound_window / 2.0) + (tile_size / 2.0)        
+    }
+    Vec2::new(
+        convert(pos.x as f32, window.width() as f32, ARENA_WIDTH as f32),
+        convert(pos.y as f32, window.height() as f32, ARENA_HEIGHT as f32),
+    )
+}
+
+// grid_to_worldの逆で、ウィンドウ上の座標をゲーム内の位置（マスの途中も含む）に変換する
+fn world_to_grid(point: Vec2, window: &Window) -> Vec2 {
+    fn convert(point: f32, bound_window: f32, bound_game: f32) -> f32 {
+        let tile_size = bound_window / (bound_game / 2.0);
+        (point + bound_window / 2.0 - tile_size / 2.0) * bound_game / bound_window
+    }
+    Vec2::new(
+        convert(point.x, window.width(), ARENA_WIDTH as f32),
+        convert(point.y, window.height(), ARENA_HEIGHT as f32),
+    )
+}
+
+fn position_translation(
+    windows: Res<Windows>,
+    interpolation: Res<tick::Interpolation>,
+    timer: Res<tick::TickTimer>,
+    mut q: Query<(&Position, &mut Transform, Option<&Layer>, Option<&tick::PreviousPosition>)>,
+) {
+    let window = windows.get_primary().unwrap();
+    for (pos, mut transform, layer, previous) in q.iter_mut() {
+        // Entityの位置をウィンドウサイズに合わせて変換してセットする
+        let z = layer.map_or(0.0, |layer| layer.0);
+        let mut translation = grid_to_world(pos, window);
+        // 滑らかに描画するときは、前のティックの位置から今の位置まで少しずつ動かす
+        // （ポータルや端を通り抜けて1マスより遠くに移ったときは、そのまま移す）
+        if let Some(tick::PreviousPosition(previous)) = previous {
+            let distance = previous.x.abs_diff(pos.x) + previous.y.abs_diff(pos.y);
+            if interpolation.0 && distance == 1 {
+                translation = grid_to_world(previous, window).lerp(translation, timer.progress());
+            }
+        }
+        transform.translation = translation.extend(z);
+    }
+}
+
+
+fn food_spawner(
+    mut commands: Commands,
+    mut writer: EventWriter<FoodSpawnEvent>,
+    arena: Res<Arena>,
+    level: Res<Level>,
+    food: Query<Entity, With<Food>>,
+    hazards: Query<(&Position, &Hazard)>,
+    segments: Query<&Position, With<SnakeSegment>>,
+) {
+    // まだFoodが存在していなければ、壁やポータルやスネークのないランダムな場所に新しいFoodを生成する
+    if food.iter().next().is_some() {
+        return;
+    }
+    // ハザードが今いるマスと次に移動するマスも避ける
+    let blocked: Vec<Position> = hazards
+        .iter()
+        .flat_map(|(pos, hazard)| [*pos, hazard.next_position()])
+        .chain(segments.iter().copied())
+        .collect();
+    let position = match arena.random_position_where(0, |pos| {
+        !level.blocks(pos) && !blocked.contains(pos)
+    }) {
+        Some(position) => position,
+        None => return,
+    };
+    commands
+        .spawn_bundle(SpriteBundle::default())
+        .insert(Food)
+        .insert(position)
+        .insert(Size::square(0.8));
+    writer.send(FoodSpawnEvent);
+}
+
+// 1ティックごとに動かすSystem（テストでは固定タイムステップなしで1回ずつ動かす）
+fn movement_systems() -> SystemSet {
+    SystemSet::new()
+        // 動く前の位置を覚えておく（滑らかに描画するのに使う）
+        .with_system(tick::remember_positions.before(snake_movement))
+        // Snakeの移動を処理する
+        .with_system(snake_movement)
+        // SnakeがFoodを食べたときの処理をする
+        .with_system(snake_eating.after(snake_movement))
+        // Snakeが成長したときの処理をする
+        .with_system(snake_growth.after(snake_eating))
+        // ゲームモードごとのティック処理をする
+        .with_system(modes::mode_tick.after(snake_growth))
+        // 空腹ゲージを減らし、空になったら尻尾を失う
+        .with_system(hunger::hunger_tick.after(snake_growth))
+        // バトルロイヤルでアリーナを縮める
+        .with_system(arena::shrink_arena.after(modes::mode_tick))
+}
+
+// ゲームのルール（ウィンドウ版とターミナル版で共通のリソース、イベント、System）を登録する
+fn add_game_rules(app: &mut App) -> &mut App {
+    // コマンドライン引数からゲームモードを選ぶ
+    let mode = modes::mode_from_args();
+    // --board があれば、その盤面からレベルを作る
+    let board = StartingBoard::from_args();
+    let level = match &board.0 {
+        Some(board) => board.level().unwrap_or_else(|err| {
+            warn!("Failed to use board: {}", err);
+            Level::default()
+        }),
+        None => level::level_from_args(),
+    };
+    // 設定ファイルを読み込む（読み込むのはここだけで、他ではSettingsのリソースを読む）
+    let settings = Settings::load();
+
+    app
+        // アリーナの枠とレベルの壁とポータルを生成するStartupSystemを登録する
+        .add_startup_system(level::setup_level)
+        // Snakeを生成するStartupSystemを登録する
+        .add_startup_system(setup_snake)
+        // Snakeのセグメントを管理するSnakeSegmentsを初期化する
+        .insert_resource(SnakeSegments::default())
+        // 最後尾のSnakeSegmentの位置を管理するLastTailPositionを初期化する
+        .insert_resource(LastTailPosition::default())
+        // スコアを0で初期化する
+        .insert_resource::<u32>(0)
+        // 残機を設定ファイルから読み込む（--livesで変える）
+        .insert_resource(Lives::from_args(settings.lives))
+        // コマンドライン引数からゲームモードを選ぶ
+        .insert_resource(mode)
+        // コマンドライン引数からレベルを読み込む
+        .insert_resource(level)
+        .insert_resource(board)
+        // スネークが動ける範囲を初期化する
+        .insert_resource(Arena::with_border(settings.border_width))
+        // 枠の厚さ（次のプレイから）と端の扱いを設定ファイルから読み込む
+        .insert_resource(settings.border_width)
+        .insert_resource(settings.wall_mode)
+        // スネークが動く間隔を設定ファイルから読み込む
+        .insert_resource(tick::TickTimer::new(settings.tick_millis))
+        // 端を通り抜けるときは外側の枠の壁を隠す
+        .add_system(arena::show_border)
+        // 空腹ゲージを使うかどうかを決める
+        .insert_resource(HungerRule::from_args(mode))
+        // プレイの経過時間とティック数を初期化する
+        .insert_resource(RunClock::default())
+        // 保存されたハイスコアを読み込む
+        .insert_resource(HighScores::load())
+        // 成長イベントを登録する
+        .add_event::<GrowthEvent>()
+        // ゲームパッドをプレイヤーに割り当てる（ゲームパッドの入力はウィンドウ版だけで読む）
+        .insert_resource(Controllers::default())
+        // 設定ファイルからキーとボタンの割り当てと向きの変え方を読み込む（--relativeで相対操作にする）
+        .insert_resource(settings.bindings.clone().with_missing_defaults())
+        .insert_resource(ControlScheme::from_args(settings.control_scheme))
+        // 設定の画面で変えた値を書き込んで保存する
+        .insert_resource(settings)
+        // ゲームの画面から始める（他の画面を開いている間はゲームを止める）
+        .insert_resource(Screen::default())
+        // Snakeの動きを処理するSystemを登録する（曲がったティックはキーとクリックやタッチで共通）
+        .insert_resource(LastTurns::default())
+        .add_system(
+            snake_movement_input
+                .with_run_criteria(screen::playing)
+                .before(snake_movement),
+        )
+        // 割り当てたキーでプレイをやり直す
+        .add_system(restart_on_action.with_run_criteria(screen::playing))
+        // 割り当てたキーで一時停止と再開をする（デバッグビルドでは一時停止中に1ティックずつ進められる）
+        .insert_resource(screen::SingleStep::default())
+        .add_system_to_stage(CoreStage::First, screen::begin_step)
+        .add_system(pause::toggle_pause)
+        .add_system(pause::step_tick)
+        // ゲームオーバーイベントを登録する
+        .add_event::<GameOverEvent>()
+        // Snakeの移動、食事、成長を処理するSystemSetを登録する
+        .add_system_set(movement_systems()
+                .with_run_criteria(tick::movement_tick.chain(screen::while_playing_or_stepping)))
+        // 新しいSnakeHeadに空腹ゲージを付ける
+        .add_system(hunger::attach_hunger)
+        // プレイ終了イベントを登録する
+        .add_event::<RunOverEvent>()
+        .add_event::<StartRunEvent>()
+        // プレイの経過時間を計測する
+        .add_system(modes::run_clock.with_run_criteria(screen::playing))
+        // 食べたFoodの数と一番速かった速さを数える
+        .insert_resource(summary::RunStats::default())
+        .add_system(summary::track_run_stats.with_run_criteria(screen::playing))
+        // 直近の盤面を覚える（ウィンドウ版ではゲームオーバーの画面のミニリプレイに使う）
+        .insert_resource(summary::RecentBoards::default())
+        // ゲームオーバー時の処理をする
+        .add_system(
+            game_over
+                .after(snake_movement)
+                .after(hunger::hunger_tick)
+                .after(arena::shrink_arena)
+                .after(hazard::move_hazards),
+        )
+        // プレイ終了時にハイスコアを記録する
+        .add_system(
+            modes::record_high_score
+                .after(game_over)
+                .after(modes::run_clock)
+                .after(modes::mode_tick),
+        )
+        // プレイ終了時にゲームをリセットする
+        .add_system(restart_run.after(modes::record_high_score))
+        // 無敵中のスネークを点滅させ、無敵が切れたら外す（止めている間は無敵の時間も止める）
+        .add_system(lives::blink_invulnerable.with_run_criteria(screen::playing))
+        // ハザードを独自の固定タイムステップで動かす
+        .add_system_set(
+            SystemSet::new()
+                .with_run_criteria(
+                    FixedTimestep::step(HAZARD_STEP).chain(screen::while_playing_or_stepping),
+                )
+                .with_system(hazard::move_hazards),
+        )
+        // Foodの生成イベントを登録する
+        .add_event::<FoodSpawnEvent>()
+        // Foodを生成するSystemSetを登録する
+        .add_system_set(
+            SystemSet::new()
+                // 一定時間ごとにFoodを生成する
+                .with_run_criteria(FixedTimestep::step(0.5).chain(screen::while_playing_or_stepping))
+                // スネークが動いた後の位置を避けて置く（先に置くと、動いたヘッドと重なることがある）
+                .with_system(food_spawner.after(snake_movement)),
+        )
+        // --record があれば、ティックごとの盤面を記録して終了時に保存する
+        .insert_resource(replay::ReplayRecorder::from_args())
+        .add_system_to_stage(CoreStage::PostUpdate, replay::record_frame)
+        .add_system_to_stage(CoreStage::Last, replay::save_replay)
+}
+
+// ウィンドウ版のゲームを起動する（ターミナル版は src/bin/snake-tui.rs から tui::run で起動する）
+pub fn run() {
+    // --export があれば、記録したプレイを画像に書き出して終了する
+    if let Some((replay_path, output)) = replay::export_from_args() {
+        if let Err(err) = replay::export(&replay_path, &output) {
+            eprintln!("Failed to export replay: {}", err);
+            std::process::exit(1);
+        }
+        return;
+    }
+    // --render-sounds があれば、効果音とBGMをWAVに書き出して終了する
+    if let Some(dir) = audio::render_sounds_from_args() {
+        if let Err(err) = audio::render_sounds(&dir) {
+            eprintln!("Failed to render sounds: {}", err);
+            std::process::exit(1);
+        }
+        return;
+    }
+    // Appを作成する
+    let mut app = App::new();
+    add_game_rules(&mut app);
+    // 読み込んだ設定からウィンドウの表示方法と、滑らかに描画するかどうかとテーマを決める
+    let settings = app.world.resource::<Settings>();
+    let window_mode = settings.display_mode.window_mode();
+    let present_mode = settings::present_mode(settings.vsync);
+    let interpolation = tick::Interpolation(settings.interpolation);
+    let themes = theme::themes_from_args(settings);
+    let effects = effects::Effects::from_args(settings);
+    // 効果音とBGMを鳴らす（--no-audioで音を出さない）
+    audio::add_audio(&mut app);
+    // メニューのボタンとリストを使えるようにする
+    widgets::add_widgets(&mut app);
+    app
+        // メインメニューから始める
+        .insert_resource(Screen::Menu)
+        .insert_resource(menu::Menu::new(
+            modes::mode_from_args(),
+            &StartingBoard::from_args(),
+        ))
+        // Escでメニューを開き、メニューのページを表示して、ボタンの操作を処理する
+        // 同じキーで画面を続けて移らないよう、画面を移るSystemをボタンの操作より先に動かす
+        .add_system(
+            menu::open_menu
+                .with_run_criteria(screen::playing)
+                .before(widgets::navigate),
+        )
+        .add_system(menu::menu_actions.after(widgets::navigate))
+        // 設定の画面で値を変えてすぐに反映し、閉じたら保存する
+        .insert_resource(settings::SettingsMenu::default())
+        .insert_resource(interpolation)
+        .add_system(settings::settings_actions.after(widgets::navigate))
+        // 一時停止したらメニューを表示する
+        .add_system(pause::pause_actions.after(widgets::navigate))
+        // プレイが終わったら、リセットされる前にプレイをまとめてゲームオーバーの画面を開く
+        .insert_resource(summary::LastRun::default())
+        .add_system_to_stage(CoreStage::PostUpdate, summary::record_recent_boards)
+        .add_system(
+            summary::summarize_run
+                .after(game_over)
+                .after(modes::run_clock)
+                .after(modes::mode_tick)
+                .before(restart_run),
+        )
+        .add_system(summary::summary_actions.after(widgets::navigate))
+        .add_system(summary::play_replay)
+        // 画面が変わったら選んでいるボタンを戻してから、開いた画面のUIを作る
+        .add_system(
+            widgets::reset_focus
+                .after(menu::open_menu)
+                .after(menu::menu_actions)
+                .after(settings::settings_actions)
+                .after(pause::toggle_pause)
+                .after(pause::pause_actions)
+                .after(summary::summarize_run)
+                .after(summary::summary_actions)
+                .after(bindings::open_bindings)
+                .after(bindings::bindings_actions),
+        )
+        .add_system(menu::show_menu.after(widgets::reset_focus))
+        .add_system(settings::show_settings.after(widgets::reset_focus))
+        .add_system(pause::show_pause.after(widgets::reset_focus))
+        .add_system(summary::show_summary.after(widgets::reset_focus))
+        // テーマを読み込む（背景色や各オブジェクトの色はapply_themeで設定する）
+        .insert_resource(themes)
+        .insert_resource(WindowDescriptor {
+            // ウィンドウのタイトルを設定する
+            title: "Snake-rust".to_string(),
+            // ウィンドウのサイズを設定する
+            width: 1200.0,
+            height: 900.0,
+            mode: window_mode,
+            present_mode,
+            ..default()
+        })
+        // カメラをセットアップするStartupSystemを登録する
+        .add_startup_system(setup_camera)
+        // スネークとFoodのスプライトシートはテーマに合わせて読み込む
+        .insert_resource(SnakeSpriteSheet::default())
+        // アリーナのグリッドを生成し、アリーナの大きさが変わったら作り直す（--gridで最初から表示する）
+        .insert_resource(ShowGrid::from_args())
+        .add_system(arena::rebuild_grid)
+        // 食事や死亡の演出を設定ファイルから初期化する（--no-effectsで全て切る）
+        .insert_resource(effects)
+        .insert_resource(effects::ScreenShake::default())
+        // Foodを食べたらパーティクルとスコアのポップアップを出し、長さが10の倍数になったらレベルアップの演出をする
+        .add_system(effects::eat_effects.after(snake_growth))
+        // 死んだら画面を揺らし、スネークを消していく（game_overがスネークを削除する前に読む）
+        .add_system(
+            effects::death_effects
+                .after(snake_movement)
+                .after(hunger::hunger_tick)
+                .after(arena::shrink_arena)
+                .after(hazard::move_hazards)
+                .before(game_over),
+        )
+        .add_system(effects::update_particles)
+        .add_system(effects::update_popups)
+        .add_system(effects::update_dissolve)
+        .add_system(effects::shake_camera)
+        // クリックやタッチのスワイプでも向きを変える
+        .add_system(
+            pointer::pointer_input
+                .with_run_criteria(screen::playing)
+                .before(snake_movement),
+        )
+        // ゲームパッドが抜き差しされたらプレイヤーへの割り当てを更新する
+        .add_system(gamepad::assign_gamepads)
+        // スコアと残機の表示を更新する
+        .add_system(scoreboard)
+        // PostUpdateステージに位置の変換やサイズの調整をするSystemを登録する
+        .add_system_set_to_stage(
+            CoreStage::PostUpdate,
+            SystemSet::new()
+                // 位置を画面に合わせて変換する
+                .with_system(position_translation)
+                // サイズを画面に合わせて調整する
+                .with_system(size_scaling)
+                // スネークの各セグメントのスプライトを前後のつながりに合わせて選ぶ
+                .with_system(sprites::update_snake_sprites)
+                // テーマの色でスプライトと背景とテキストを塗る
+                .with_system(theme::apply_theme),
+        )
+        // テーマのスプライトシートが読み込めたら単色の描画から差し替える
+        .add_system(sprites::apply_sprite_sheet)
+        // Tキーでテーマを切り替える
+        .add_system(theme::cycle_theme)
+        // Gキーでグリッドの表示を切り替える
+        .add_system(arena::toggle_grid)
+        // F1でキーとボタンの割り当てを変える画面を開く
+        .insert_resource(bindings::BindingsMenu::default())
+        .add_system(
+            bindings::open_bindings
+                .with_run_criteria(screen::playing)
+                .before(widgets::navigate),
+        )
+        .add_system(bindings::bindings_actions.after(widgets::navigate))
+        .add_system(bindings::show_bindings.after(widgets::reset_focus))
+        // Bキーで今の盤面をASCIIで出力する
+        .add_system(snapshot::print_board)
+        // デフォルトプラグインを追加する
+        .add_plugins(DefaultPlugins)
+        // アプリを実行する
+        .run();
+}
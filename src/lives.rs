@@ -0,0 +1,84 @@
+use bevy::prelude::*;
+
+use crate::SnakeSegment;
+
+// リスポーン後の無敵時間（秒）
+const INVULNERABLE_SECONDS: f32 = 2.0;
+// 無敵中に点滅する間隔（秒）
+const BLINK_INTERVAL: f32 = 0.1;
+
+// 設定で選べる残機の範囲
+pub const MIN_LIVES: u32 = 1;
+pub const MAX_LIVES: u32 = 9;
+
+// 残機を保持するリソース
+pub struct Lives {
+    pub remaining: u32,
+    max: u32,
+}
+
+impl Lives {
+    pub fn new(max: u32) -> Self {
+        Self {
+            remaining: max,
+            max,
+        }
+    }
+
+    // コマンドライン引数の --lives があれば、保存された設定より優先する
+    pub fn from_args(saved: u32) -> Self {
+        let args: Vec<String> = std::env::args().collect();
+        let max = args
+            .iter()
+            .position(|arg| arg == "--lives")
+            .and_then(|i| args.get(i + 1))
+            .and_then(|value| value.parse::<u32>().ok())
+            .unwrap_or(saved);
+        Self::new(max.clamp(MIN_LIVES, MAX_LIVES))
+    }
+
+    pub fn max(&self) -> u32 {
+        self.max
+    }
+
+    // 最大値を変える（次のプレイから）
+    pub fn set_max(&mut self, max: u32) {
+        self.max = max.clamp(MIN_LIVES, MAX_LIVES);
+    }
+
+    // 残機を最大値に戻す
+    pub fn reset(&mut self) {
+        self.remaining = self.max;
+    }
+}
+
+// リスポーン直後の無敵状態を表すコンポーネント
+#[derive(Component)]
+pub struct Invulnerable(Timer);
+
+impl Invulnerable {
+    pub fn new() -> Self {
+        Self(Timer::from_seconds(INVULNERABLE_SECONDS, false))
+    }
+}
+
+pub fn blink_invulnerable(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut heads: Query<(Entity, &mut Invulnerable)>,
+    mut segments: Query<&mut Visibility, With<SnakeSegment>>,
+) {
+    // 無敵中はスネーク全体を点滅させ、時間が切れたら無敵を解除する
+    for (head, mut invulnerable) in heads.iter_mut() {
+        invulnerable.0.tick(time.delta());
+        let finished = invulnerable.0.finished();
+        let visible =
+            finished || (invulnerable.0.elapsed_secs() / (2.0 * BLINK_INTERVAL)).fract() < 0.5;
+        for mut visibility in segments.iter_mut() {
+            visibility.is_visible = visible;
+        }
+        if finished {
+            commands.entity(head).remove::<Invulnerable>();
+        }
+    }
+}
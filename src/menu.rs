@@ -0,0 +1,250 @@
+use bevy::app::AppExit;
+use bevy::prelude::*;
+
+use crate::hunger::HungerRule;
+use crate::level::{self, Level};
+use crate::modes::{GameMode, HighScores, StartRunEvent};
+use crate::screen::Screen;
+use crate::settings::SettingsMenu;
+use crate::snapshot::StartingBoard;
+use crate::theme::Themes;
+use crate::widgets::{self, Focus, ScreenEvents, WidgetStyle};
+
+// リストの枠の高さ（ピクセル）
+const LIST_HEIGHT: f32 = 400.0;
+
+// メインメニューのボタン（上から順）
+const PLAY: usize = 0;
+const MODE: usize = 1;
+const LEVEL: usize = 2;
+const HIGH_SCORES: usize = 3;
+const SETTINGS: usize = 4;
+const QUIT: usize = 5;
+
+// メニューのページ
+#[derive(Clone, Copy, PartialEq, Eq, Default)]
+pub enum MenuPage {
+    #[default]
+    Main,
+    Modes,
+    Levels,
+    HighScores,
+}
+
+// メニューで選んでいるページと、次のプレイで使うモードとレベル
+pub struct Menu {
+    page: MenuPage,
+    mode: GameMode,
+    level: Option<String>,
+    // レベルを選び直したか（選び直していなければ --level や --board のレベルのままにする）
+    level_chosen: bool,
+    // 盤面から作ったレベルを使っているか
+    board: bool,
+}
+
+impl Menu {
+    pub fn new(mode: GameMode, board: &StartingBoard) -> Self {
+        Self {
+            page: MenuPage::Main,
+            mode,
+            level: level::level_name_from_args(),
+            level_chosen: false,
+            board: board.0.is_some(),
+        }
+    }
+
+    fn level_label(&self) -> &str {
+        match &self.level {
+            Some(name) => name,
+            None if self.board && !self.level_chosen => "board",
+            None => "none",
+        }
+    }
+
+    // ページを移り、戻ったときはページを開いたボタンを選ぶ
+    fn open(&mut self, page: MenuPage, focus: &mut Focus) {
+        let from = self.page;
+        self.page = page;
+        focus.index = match (from, page) {
+            (MenuPage::Modes, MenuPage::Main) => MODE,
+            (MenuPage::Levels, MenuPage::Main) => LEVEL,
+            (MenuPage::HighScores, MenuPage::Main) => HIGH_SCORES,
+            _ => 0,
+        };
+    }
+}
+
+// メニューのUIの一番上のノード
+#[derive(Component)]
+pub struct MenuRoot;
+
+// ゲーム中にEscでメニューを開く
+pub fn open_menu(keys: Res<Input<KeyCode>>, mut screen: ResMut<Screen>, mut menu: ResMut<Menu>) {
+    if keys.just_pressed(KeyCode::Escape) {
+        *screen = Screen::Menu;
+        menu.page = MenuPage::Main;
+    }
+}
+
+// 画面かページが変わったら、メニューのUIを作り直す
+pub fn show_menu(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    themes: Res<Themes>,
+    screen: Res<Screen>,
+    menu: Res<Menu>,
+    high_scores: Res<HighScores>,
+    mut focus: ResMut<Focus>,
+    roots: Query<Entity, With<MenuRoot>>,
+) {
+    if !screen.is_changed() && !menu.is_changed() {
+        return;
+    }
+    for root in roots.iter() {
+        commands.entity(root).despawn_recursive();
+    }
+    if *screen != Screen::Menu {
+        return;
+    }
+
+    let style = WidgetStyle::from_theme(themes.current(), &asset_server);
+    let mut count = 0;
+    commands
+        .spawn_bundle(widgets::screen_root(&style))
+        .insert(MenuRoot)
+        .with_children(|parent| match menu.page {
+            MenuPage::Main => {
+                widgets::title(parent, "SNAKE", &style);
+                let items = [
+                    "Play".to_string(),
+                    format!("Mode: {}", menu.mode),
+                    format!("Level: {}", menu.level_label()),
+                    "High scores".to_string(),
+                    "Settings".to_string(),
+                    "Quit".to_string(),
+                ];
+                for (i, item) in items.iter().enumerate() {
+                    widgets::button(parent, i, item, &style);
+                }
+                count = items.len();
+            }
+            MenuPage::Modes => {
+                widgets::title(parent, "MODE", &style);
+                widgets::scrolling_list(parent, LIST_HEIGHT, |parent| {
+                    for (i, mode) in GameMode::ALL.iter().enumerate() {
+                        widgets::button(parent, i, &mode.to_string(), &style);
+                    }
+                });
+                count = GameMode::ALL.len();
+            }
+            MenuPage::Levels => {
+                widgets::title(parent, "LEVEL", &style);
+                // 先頭は何もないアリーナ
+                let names = level::level_names();
+                widgets::scrolling_list(parent, LIST_HEIGHT, |parent| {
+                    widgets::button(parent, 0, "none", &style);
+                    for (i, name) in names.iter().enumerate() {
+                        widgets::button(parent, i + 1, name, &style);
+                    }
+                });
+                count = names.len() + 1;
+            }
+            MenuPage::HighScores => {
+                widgets::title(parent, "HIGH SCORES", &style);
+                widgets::scrolling_list(parent, LIST_HEIGHT, |parent| {
+                    for mode in GameMode::ALL {
+                        let records: Vec<String> = high_scores
+                            .records(mode)
+                            .iter()
+                            .map(|record| mode.format_record(*record))
+                            .collect();
+                        let records = if records.is_empty() {
+                            "-".to_string()
+                        } else {
+                            records.join("  ")
+                        };
+                        widgets::label(parent, &format!("{}: {}", mode, records), &style);
+                    }
+                });
+                widgets::button(parent, 0, "Back", &style);
+                count = 1;
+            }
+        });
+    // ページを開いた直後はボタンの数だけ変え、選んでいるボタンはopenで決めたものにする
+    focus.count = count;
+    focus.index = focus.index.min(count - 1);
+}
+
+// メニューのボタンが押されたときと、戻る操作をしたときの処理
+pub fn menu_actions(
+    mut events: ScreenEvents,
+    mut screen: ResMut<Screen>,
+    mut menu: ResMut<Menu>,
+    mut focus: ResMut<Focus>,
+    mut settings_menu: ResMut<SettingsMenu>,
+    mut mode: ResMut<GameMode>,
+    mut hunger_rule: ResMut<HungerRule>,
+    mut level: ResMut<Level>,
+    mut start_writer: EventWriter<StartRunEvent>,
+    mut exit_writer: EventWriter<AppExit>,
+) {
+    let input = match events.read(&screen, Screen::Menu) {
+        Some(input) => input,
+        None => return,
+    };
+    if input.back {
+        match menu.page {
+            // メインメニューから戻るとゲームを続ける
+            MenuPage::Main => *screen = Screen::Playing,
+            _ => menu.open(MenuPage::Main, &mut focus),
+        }
+        return;
+    }
+    let index = match input.activated {
+        Some(index) => index,
+        None => return,
+    };
+    match menu.page {
+        MenuPage::Main => match index {
+            PLAY => {
+                // 選んだモードとレベルで新しいプレイを始める
+                *mode = menu.mode;
+                *hunger_rule = HungerRule::from_args(menu.mode);
+                if menu.level_chosen {
+                    *level = level::load_or_default(menu.level.as_deref());
+                }
+                start_writer.send(StartRunEvent);
+                *screen = Screen::Playing;
+            }
+            MODE => {
+                menu.open(MenuPage::Modes, &mut focus);
+                focus.index = GameMode::ALL
+                    .iter()
+                    .position(|mode| *mode == menu.mode)
+                    .unwrap_or_default();
+            }
+            LEVEL => menu.open(MenuPage::Levels, &mut focus),
+            HIGH_SCORES => menu.open(MenuPage::HighScores, &mut focus),
+            SETTINGS => {
+                // 設定の画面を開き、閉じたらメニューに戻る
+                *settings_menu = SettingsMenu::returning_to(Screen::Menu);
+                *screen = Screen::Settings;
+            }
+            QUIT => exit_writer.send(AppExit),
+            _ => {}
+        },
+        MenuPage::Modes => {
+            menu.mode = GameMode::ALL[index];
+            menu.open(MenuPage::Main, &mut focus);
+        }
+        MenuPage::Levels => {
+            menu.level = match index {
+                0 => None,
+                _ => level::level_names().get(index - 1).cloned(),
+            };
+            menu.level_chosen = true;
+            menu.open(MenuPage::Main, &mut focus);
+        }
+        MenuPage::HighScores => menu.open(MenuPage::Main, &mut focus),
+    }
+}
@@ -0,0 +1,274 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::str::FromStr;
+
+use bevy::core::Stopwatch;
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::arena;
+use crate::hunger::Hunger;
+use crate::SnakeSegments;
+
+// タイムアタックの制限時間（秒）
+const TIME_ATTACK_SECONDS: f32 = 120.0;
+// ターゲットレングスで目指す長さ
+const TARGET_LENGTH: usize = 20;
+// モードごとに保存するハイスコアの件数
+const HIGH_SCORES_PER_MODE: usize = 5;
+// ハイスコアを保存するファイル
+const HIGH_SCORES_PATH: &str = "highscores.ron";
+
+// ゲームモード
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Default, Serialize, Deserialize)]
+pub enum GameMode {
+    // 終わりのない通常モード
+    #[default]
+    Endless,
+    // 制限時間内にスコアを稼ぐ
+    TimeAttack,
+    // 生き延びたティック数がスコアになる
+    Survival,
+    // 目標の長さに到達するまでの時間を競う
+    TargetLength,
+    // 一定時間ごとにアリーナが縮む
+    BattleRoyale,
+}
+
+impl GameMode {
+    pub const ALL: [GameMode; 5] = [
+        GameMode::Endless,
+        GameMode::TimeAttack,
+        GameMode::Survival,
+        GameMode::TargetLength,
+        GameMode::BattleRoyale,
+    ];
+
+    // Foodを食べたときにスコアが加算されるかどうか
+    pub fn scores_food(self) -> bool {
+        self != GameMode::Survival
+    }
+
+    // 空腹ゲージを常に使うかどうか（サバイバルでは食べないと餓死する）
+    pub fn uses_hunger(self) -> bool {
+        self == GameMode::Survival
+    }
+
+    // 記録は小さいほど良いかどうか（ターゲットレングスはタイムで競う）
+    fn lower_is_better(self) -> bool {
+        self == GameMode::TargetLength
+    }
+
+    // 記録を表示用の文字列にする
+    pub fn format_record(self, record: u32) -> String {
+        if self.lower_is_better() {
+            format!("{:.1}s", record as f32 / 1000.0)
+        } else {
+            record.to_string()
+        }
+    }
+}
+
+impl fmt::Display for GameMode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            GameMode::Endless => "endless",
+            GameMode::TimeAttack => "time-attack",
+            GameMode::Survival => "survival",
+            GameMode::TargetLength => "target-length",
+            GameMode::BattleRoyale => "battle-royale",
+        })
+    }
+}
+
+impl FromStr for GameMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        GameMode::ALL
+            .into_iter()
+            .find(|mode| mode.to_string() == s)
+            .ok_or_else(|| format!("unknown game mode: {}", s))
+    }
+}
+
+// コマンドライン引数の --mode からゲームモードを決める
+pub fn mode_from_args() -> GameMode {
+    let args: Vec<String> = std::env::args().collect();
+    args.iter()
+        .position(|arg| arg == "--mode")
+        .and_then(|i| args.get(i + 1))
+        .map(|name| {
+            name.parse().unwrap_or_else(|err| {
+                warn!("{}", err);
+                GameMode::default()
+            })
+        })
+        .unwrap_or_default()
+}
+
+// 1回のプレイ（残機がなくなるか、モードの終了条件を満たすまで）が終わったときにトリガーするイベント
+pub struct RunOverEvent;
+
+// メニューから新しいプレイを始めるときにトリガーするイベント（前のプレイの記録は残さない）
+pub struct StartRunEvent;
+
+// 現在のプレイの経過時間とティック数を保持
+#[derive(Default)]
+pub struct RunClock {
+    pub elapsed: Stopwatch,
+    pub ticks: u32,
+}
+
+impl RunClock {
+    // タイムアタックの残り時間（秒）
+    pub fn time_left(&self) -> f32 {
+        (TIME_ATTACK_SECONDS - self.elapsed.elapsed_secs()).max(0.0)
+    }
+}
+
+// モードごとのハイスコア
+#[derive(Default, Serialize, Deserialize)]
+pub struct HighScores(HashMap<GameMode, Vec<u32>>);
+
+impl HighScores {
+    // ファイルからハイスコアを読み込む。読み込めなければ空にする
+    pub fn load() -> Self {
+        fs::read_to_string(HIGH_SCORES_PATH)
+            .ok()
+            .and_then(|contents| ron::from_str(&contents).ok())
+            .unwrap_or_default()
+    }
+
+    fn save(&self) {
+        let result = ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default())
+            .map_err(|err| err.to_string())
+            .and_then(|contents| {
+                fs::write(HIGH_SCORES_PATH, contents).map_err(|err| err.to_string())
+            });
+        if let Err(err) = result {
+            warn!("Failed to save high scores: {}", err);
+        }
+    }
+
+    // 良い順に並べた記録
+    pub fn records(&self, mode: GameMode) -> &[u32] {
+        self.0.get(&mode).map(Vec::as_slice).unwrap_or_default()
+    }
+
+    pub fn best(&self, mode: GameMode) -> Option<u32> {
+        self.0.get(&mode).and_then(|records| records.first().copied())
+    }
+
+    // 記録を追加し、上位の記録だけを残す
+    fn submit(&mut self, mode: GameMode, record: u32) {
+        let records = self.0.entry(mode).or_default();
+        records.push(record);
+        if mode.lower_is_better() {
+            records.sort_unstable();
+        } else {
+            records.sort_unstable_by(|a, b| b.cmp(a));
+        }
+        records.truncate(HIGH_SCORES_PER_MODE);
+    }
+}
+
+pub fn run_clock(
+    time: Res<Time>,
+    mode: Res<GameMode>,
+    mut clock: ResMut<RunClock>,
+    mut run_over_writer: EventWriter<RunOverEvent>,
+) {
+    clock.elapsed.tick(time.delta());
+    // タイムアタックは制限時間を過ぎたら終了する
+    if *mode == GameMode::TimeAttack && clock.time_left() <= 0.0 && !clock.elapsed.paused() {
+        clock.elapsed.pause();
+        run_over_writer.send(RunOverEvent);
+    }
+}
+
+pub fn mode_tick(
+    mode: Res<GameMode>,
+    segments: Res<SnakeSegments>,
+    mut clock: ResMut<RunClock>,
+    mut score: ResMut<u32>,
+    mut run_over_writer: EventWriter<RunOverEvent>,
+) {
+    clock.ticks += 1;
+    match *mode {
+        GameMode::Survival => {
+            // 生き延びたティック数がスコアになる
+            *score += 1;
+        }
+        GameMode::TargetLength => {
+            // 目標の長さに到達したら終了する
+            if segments.len() >= TARGET_LENGTH && !clock.elapsed.paused() {
+                clock.elapsed.pause();
+                run_over_writer.send(RunOverEvent);
+            }
+        }
+        GameMode::Endless | GameMode::TimeAttack | GameMode::BattleRoyale => {}
+    }
+}
+
+pub fn record_high_score(
+    mode: Res<GameMode>,
+    score: Res<u32>,
+    segments: Res<SnakeSegments>,
+    clock: Res<RunClock>,
+    mut high_scores: ResMut<HighScores>,
+    mut reader: EventReader<RunOverEvent>,
+) {
+    if reader.iter().next().is_none() {
+        return;
+    }
+    let record = match *mode {
+        // 目標の長さに到達できなかった場合は記録しない
+        GameMode::TargetLength if segments.len() < TARGET_LENGTH => return,
+        GameMode::TargetLength => clock.elapsed.elapsed().as_millis() as u32,
+        _ => *score,
+    };
+    info!("Run over! {}: {}", *mode, mode.format_record(record));
+    high_scores.submit(*mode, record);
+    high_scores.save();
+}
+
+// モードに応じたHUDの文字列を作る
+pub fn hud_text(
+    mode: GameMode,
+    score: u32,
+    lives: u32,
+    length: usize,
+    hunger: Option<&Hunger>,
+    clock: &RunClock,
+    high_scores: &HighScores,
+) -> String {
+    let progress = match mode {
+        GameMode::Endless => format!("Score: {}", score),
+        GameMode::TimeAttack => {
+            let left = clock.time_left().ceil() as u32;
+            format!("Score: {}  Time: {}:{:02}", score, left / 60, left % 60)
+        }
+        GameMode::Survival => format!("Score: {}", score),
+        GameMode::TargetLength => format!(
+            "Length: {}/{}  Time: {:.1}s",
+            length,
+            TARGET_LENGTH,
+            clock.elapsed.elapsed_secs()
+        ),
+        GameMode::BattleRoyale => format!(
+            "Score: {}  Shrink: {}",
+            score,
+            arena::ticks_until_shrink(clock)
+        ),
+    };
+    let best = high_scores
+        .best(mode)
+        .map(|record| mode.format_record(record))
+        .unwrap_or_else(|| "-".to_string());
+    let hunger = hunger
+        .map(|hunger| format!("  Hunger: {}", hunger.meter()))
+        .unwrap_or_default();
+    format!("{}  Lives: {}{}  Best: {}", progress, lives, hunger, best)
+}
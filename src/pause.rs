@@ -0,0 +1,108 @@
+use bevy::app::AppExit;
+use bevy::prelude::*;
+
+use crate::bindings::{Action, Actions};
+use crate::modes::StartRunEvent;
+use crate::screen::{Screen, SingleStep};
+use crate::settings::SettingsMenu;
+use crate::theme::Themes;
+use crate::widgets::{self, ScreenEvents, WidgetStyle};
+
+// 一時停止のメニューのボタン（上から順）
+const RESUME: usize = 0;
+const RESTART: usize = 1;
+const SETTINGS: usize = 2;
+const QUIT: usize = 3;
+
+// 一時停止のメニューのUIの一番上のノード
+#[derive(Component)]
+pub struct PauseRoot;
+
+// 割り当てたキー（PかStart）で一時停止し、もう一度押すと再開する
+pub fn toggle_pause(actions: Actions, mut screen: ResMut<Screen>) {
+    if !actions.just_pressed(Action::Pause, 0) {
+        return;
+    }
+    match *screen {
+        Screen::Playing => *screen = Screen::Paused,
+        Screen::Paused => *screen = Screen::Playing,
+        Screen::GameOver | Screen::Menu | Screen::Settings | Screen::Bindings => {}
+    }
+}
+
+// デバッグビルドでは、一時停止中に割り当てたキーかボタン（既定ではNかNorth）で1ティックだけ進める
+pub fn step_tick(actions: Actions, screen: Res<Screen>, mut step: ResMut<SingleStep>) {
+    if cfg!(debug_assertions) && *screen == Screen::Paused && actions.just_pressed(Action::Step, 0)
+    {
+        step.requested = true;
+    }
+}
+
+// 一時停止したら、盤面が透けて見える背景の上にメニューを表示する
+pub fn show_pause(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    themes: Res<Themes>,
+    screen: Res<Screen>,
+    mut focus: ResMut<widgets::Focus>,
+    roots: Query<Entity, With<PauseRoot>>,
+) {
+    if !screen.is_changed() {
+        return;
+    }
+    for root in roots.iter() {
+        commands.entity(root).despawn_recursive();
+    }
+    if *screen != Screen::Paused {
+        return;
+    }
+
+    // 一時停止中は盤面がよく見えるように、背景を薄くする
+    let mut style = WidgetStyle::from_theme(themes.current(), &asset_server);
+    style.background.set_a(0.5);
+    let items = ["Resume", "Restart", "Settings", "Quit"];
+    commands
+        .spawn_bundle(widgets::screen_root(&style))
+        .insert(PauseRoot)
+        .with_children(|parent| {
+            widgets::title(parent, "PAUSED", &style);
+            for (i, item) in items.iter().enumerate() {
+                widgets::button(parent, i, item, &style);
+            }
+            if cfg!(debug_assertions) {
+                widgets::label(parent, "N: step one tick", &style);
+            }
+        });
+    focus.count = items.len();
+}
+
+// 一時停止のメニューのボタンが押されたときと、戻る操作をしたときの処理
+pub fn pause_actions(
+    mut events: ScreenEvents,
+    mut screen: ResMut<Screen>,
+    mut settings_menu: ResMut<SettingsMenu>,
+    mut start_writer: EventWriter<StartRunEvent>,
+    mut exit_writer: EventWriter<AppExit>,
+) {
+    let input = match events.read(&screen, Screen::Paused) {
+        Some(input) => input,
+        None => return,
+    };
+    match input.activated {
+        Some(RESUME) => *screen = Screen::Playing,
+        Some(RESTART) => {
+            // 今のプレイを記録せずに、同じモードとレベルで始め直す
+            start_writer.send(StartRunEvent);
+            *screen = Screen::Playing;
+        }
+        Some(SETTINGS) => {
+            // 設定の画面を開き、閉じたら一時停止のメニューに戻る
+            *settings_menu = SettingsMenu::returning_to(Screen::Paused);
+            *screen = Screen::Settings;
+        }
+        Some(QUIT) => exit_writer.send(AppExit),
+        Some(_) => {}
+        None if input.back => *screen = Screen::Playing,
+        None => {}
+    }
+}
@@ -0,0 +1,107 @@
+use bevy::prelude::*;
+use bevy::utils::HashSet;
+
+use crate::bindings::ControlScheme;
+use crate::modes::RunClock;
+use crate::{try_turn, world_to_grid, Direction, LastTurns, Position, SnakeHead};
+
+// 指をこれだけ（ピクセル）動かしたらスワイプとみなす
+const SWIPE_DISTANCE: f32 = 30.0;
+// ヘッドからこれだけ（マス）離れていないところのタップやクリックは無視する
+const TAP_DEADZONE: f32 = 0.5;
+
+// 移動量のうち大きい方の軸で上下左右の向きにする
+fn dominant_direction(delta: Vec2) -> Direction {
+    if delta.x.abs() > delta.y.abs() {
+        if delta.x > 0.0 {
+            Direction::Right
+        } else {
+            Direction::Left
+        }
+    } else if delta.y > 0.0 {
+        Direction::Up
+    } else {
+        Direction::Down
+    }
+}
+
+// ウィンドウ上の点（左下が原点）が、ヘッドから見てどの向きにあるか
+fn direction_from_head(point: Vec2, head: &Position, window: &Window) -> Option<Direction> {
+    let world = point - Vec2::new(window.width(), window.height()) / 2.0;
+    let delta = world_to_grid(world, window) - Vec2::new(head.x as f32, head.y as f32);
+    if delta.abs().max_element() < TAP_DEADZONE {
+        None
+    } else {
+        Some(dominant_direction(delta))
+    }
+}
+
+// タッチの位置を、マウスと同じ左下が原点の座標にする
+// Bevyはスマートフォンでは上下を反転するが、それ以外（タッチパネルのPCなど）では上が原点のまま渡してくる
+fn touch_point(position: Vec2, window: &Window) -> Vec2 {
+    if cfg!(any(target_os = "android", target_os = "ios")) {
+        position
+    } else {
+        Vec2::new(position.x, window.height() - position.y)
+    }
+}
+
+// スワイプした向きか、クリックやタップしたところのヘッドから見た向きに曲がる
+// キーと同じく、曲がるのは1ティックに1回だけで、相対操作では左右だけでスネークから見た左右に曲がる
+pub fn pointer_input(
+    windows: Res<Windows>,
+    mouse: Res<Input<MouseButton>>,
+    touches: Res<Touches>,
+    scheme: Res<ControlScheme>,
+    clock: Res<RunClock>,
+    mut last_turns: ResMut<LastTurns>,
+    mut swiped: Local<HashSet<u64>>,
+    mut heads: Query<(&Position, &mut SnakeHead)>,
+) {
+    let window = match windows.get_primary() {
+        Some(window) => window,
+        None => return,
+    };
+    let (head_pos, mut head) = match heads.iter_mut().next() {
+        Some(head) => head,
+        None => return,
+    };
+
+    let mut dir = None;
+    if mouse.just_pressed(MouseButton::Left) {
+        dir = window
+            .cursor_position()
+            .and_then(|cursor| direction_from_head(cursor, head_pos, window));
+    }
+    // 指を動かしている途中でも、十分に動いたらすぐにスワイプとして曲がる（1回のタッチで1回だけ）
+    for touch in touches.iter() {
+        let distance =
+            touch_point(touch.position(), window) - touch_point(touch.start_position(), window);
+        if !swiped.contains(&touch.id()) && distance.length() >= SWIPE_DISTANCE {
+            swiped.insert(touch.id());
+            dir = Some(dominant_direction(distance));
+        }
+    }
+    // スワイプせずに離したらタップとして扱う
+    for touch in touches.iter_just_released() {
+        if !swiped.remove(&touch.id()) {
+            let point = touch_point(touch.position(), window);
+            dir = direction_from_head(point, head_pos, window).or(dir);
+        }
+    }
+    for touch in touches.iter_just_cancelled() {
+        swiped.remove(&touch.id());
+    }
+
+    let dir = match (*scheme, dir) {
+        (ControlScheme::Absolute, dir) => dir,
+        (ControlScheme::Relative, Some(Direction::Right)) => Some(head.direction.clockwise()),
+        (ControlScheme::Relative, Some(Direction::Left)) => {
+            Some(head.direction.counter_clockwise())
+        }
+        (ControlScheme::Relative, _) => None,
+    };
+    if let Some(dir) = dir {
+        try_turn(&mut head, dir, 0, &clock, &mut last_turns);
+    }
+}
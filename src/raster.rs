@@ -0,0 +1,106 @@
+use image::{Rgba, RgbaImage};
+
+use crate::snapshot::Snapshot;
+use crate::theme::Theme;
+use crate::Position;
+
+// スコアの数字を描く3x5ドットのフォント（上の行から、1行3ビット）
+const DIGITS: [[u8; 5]; 10] = [
+    [0b111, 0b101, 0b101, 0b101, 0b111],
+    [0b010, 0b110, 0b010, 0b010, 0b111],
+    [0b111, 0b001, 0b111, 0b100, 0b111],
+    [0b111, 0b001, 0b111, 0b001, 0b111],
+    [0b101, 0b101, 0b111, 0b001, 0b001],
+    [0b111, 0b100, 0b111, 0b001, 0b111],
+    [0b111, 0b100, 0b111, 0b101, 0b111],
+    [0b111, 0b001, 0b001, 0b001, 0b001],
+    [0b111, 0b101, 0b111, 0b101, 0b111],
+    [0b111, 0b101, 0b111, 0b001, 0b111],
+];
+
+fn rgba(color: bevy::prelude::Color) -> Rgba<u8> {
+    let channel = |c: f32| (c.clamp(0.0, 1.0) * 255.0).round() as u8;
+    let [r, g, b, a] = color.as_rgba_f32();
+    Rgba([channel(r), channel(g), channel(b), channel(a)])
+}
+
+fn fill_rect(image: &mut RgbaImage, x: u32, y: u32, width: u32, height: u32, color: Rgba<u8>) {
+    for py in y..(y + height).min(image.height()) {
+        for px in x..(x + width).min(image.width()) {
+            image.put_pixel(px, py, color);
+        }
+    }
+}
+
+// 盤面をGPUを使わずにRGBAの画像に描く
+// 1マスはcellピクセル四方で、盤面の大きさはsnapshotの幅と高さに合わせ、上にスコアを表示する帯を付ける
+pub fn render(snapshot: &Snapshot, theme: &Theme, cell: u32) -> RgbaImage {
+    let dot = (cell / 4).max(1);
+    let hud_height = dot * 7;
+    let mut image = RgbaImage::from_pixel(
+        snapshot.width * cell,
+        snapshot.height * cell + hud_height,
+        rgba(theme.background),
+    );
+
+    // 大きさsize（マスに対する割合）のタイルをマスの中央に描く（一番上の行がyの最大）
+    let mut tile = |pos: &Position, size: f32, color| {
+        if pos.x >= snapshot.width || pos.y >= snapshot.height {
+            return;
+        }
+        let side = ((cell as f32 * size).round() as u32).max(1);
+        let inset = (cell - side.min(cell)) / 2;
+        let top = hud_height + (snapshot.height - 1 - pos.y) * cell;
+        fill_rect(
+            &mut image,
+            pos.x * cell + inset,
+            top + inset,
+            side,
+            side,
+            rgba(color),
+        );
+    };
+    for pos in &snapshot.walls {
+        tile(pos, 1.0, theme.wall);
+    }
+    for (pos, pair) in &snapshot.portals {
+        tile(pos, 0.9, theme.portal(*pair));
+    }
+    for pos in &snapshot.food {
+        tile(pos, 0.8, theme.food);
+    }
+    for pos in &snapshot.hazards {
+        tile(pos, 0.8, theme.hazard);
+    }
+    // 尻尾から描いて、ヘッドが一番手前に来るようにする
+    for (i, pos) in snapshot.snake.iter().enumerate().rev() {
+        let color = if i == 0 {
+            theme.snake_head
+        } else {
+            theme.snake_body
+        };
+        tile(pos, 0.8, color);
+    }
+
+    // スコアを左上に描く
+    let text = rgba(theme.text);
+    for (i, digit) in snapshot.score.to_string().bytes().enumerate() {
+        let glyph = DIGITS[(digit - b'0') as usize];
+        let left = dot + i as u32 * dot * 4;
+        for (row, bits) in glyph.iter().enumerate() {
+            for column in 0..3 {
+                if bits & (0b100 >> column) != 0 {
+                    fill_rect(
+                        &mut image,
+                        left + column * dot,
+                        dot + row as u32 * dot,
+                        dot,
+                        dot,
+                        text,
+                    );
+                }
+            }
+        }
+    }
+    image
+}
@@ -0,0 +1,137 @@
+use std::fs::{self, File};
+use std::io::BufWriter;
+use std::path::Path;
+
+use bevy::app::AppExit;
+use bevy::prelude::*;
+use image::codecs::gif::{GifEncoder, Repeat};
+use image::{Delay, Frame};
+use serde::{Deserialize, Serialize};
+
+use crate::modes::RunClock;
+use crate::raster;
+use crate::settings::Settings;
+use crate::snapshot::{Board, Snapshot};
+use crate::theme;
+
+// 書き出す画像の1マスの大きさ（ピクセル）
+const EXPORT_CELL: u32 = 16;
+// GIFの1フレームの表示時間（ミリ秒）。スネークが1マス進む間隔に合わせる
+const GIF_DELAY_MS: u32 = 150;
+
+// 1ティックごとの盤面を並べた記録
+#[derive(Default, Serialize, Deserialize)]
+pub struct Replay {
+    pub frames: Vec<Snapshot>,
+}
+
+impl Replay {
+    pub fn load(path: &str) -> Result<Self, String> {
+        let text = fs::read_to_string(path).map_err(|err| format!("{}: {}", path, err))?;
+        ron::from_str(&text).map_err(|err| format!("{}: {}", path, err))
+    }
+
+    pub fn save(&self, path: &str) -> Result<(), String> {
+        let text = ron::to_string(self).map_err(|err| err.to_string())?;
+        fs::write(path, text).map_err(|err| format!("{}: {}", path, err))
+    }
+}
+
+// コマンドライン引数の --record で指定されたファイルに、プレイを記録する
+#[derive(Default)]
+pub struct ReplayRecorder {
+    path: Option<String>,
+    replay: Replay,
+}
+
+impl ReplayRecorder {
+    pub fn from_args() -> Self {
+        let args: Vec<String> = std::env::args().collect();
+        Self {
+            path: args
+                .iter()
+                .position(|arg| arg == "--record")
+                .and_then(|i| args.get(i + 1))
+                .cloned(),
+            replay: Replay::default(),
+        }
+    }
+}
+
+pub fn record_frame(
+    mut recorder: ResMut<ReplayRecorder>,
+    clock: Res<RunClock>,
+    mut last_tick: Local<Option<u32>>,
+    board: Board,
+) {
+    // スネークが動いたティックごとに盤面を記録する（Commandsが反映された後に読む）
+    if recorder.path.is_none() || *last_tick == Some(clock.ticks) {
+        return;
+    }
+    *last_tick = Some(clock.ticks);
+    recorder.replay.frames.push(board.snapshot());
+}
+
+pub fn save_replay(mut exit_reader: EventReader<AppExit>, recorder: Res<ReplayRecorder>) {
+    // 終了するときに記録をファイルに保存する
+    if exit_reader.iter().next().is_none() {
+        return;
+    }
+    if let Some(path) = &recorder.path {
+        match recorder.replay.save(path) {
+            Ok(()) => info!("Saved replay to {}", path),
+            Err(err) => warn!("Failed to save replay: {}", err),
+        }
+    }
+}
+
+// コマンドライン引数の --export <記録> <出力先> を読む
+pub fn export_from_args() -> Option<(String, String)> {
+    let args: Vec<String> = std::env::args().collect();
+    let i = args.iter().position(|arg| arg == "--export")?;
+    match (args.get(i + 1), args.get(i + 2)) {
+        (Some(replay), Some(output)) => Some((replay.clone(), output.clone())),
+        _ => {
+            eprintln!("usage: --export <replay.ron> <output.gif | output-directory>");
+            None
+        }
+    }
+}
+
+// 記録を今のテーマで描き、.gifならアニメーションGIFに、それ以外ならそのディレクトリに連番のPNGに書き出す
+pub fn export(replay_path: &str, output: &str) -> Result<(), String> {
+    let replay = Replay::load(replay_path)?;
+    let themes = theme::themes_from_args(&Settings::load());
+    let frames: Vec<_> = replay
+        .frames
+        .iter()
+        .map(|frame| raster::render(frame, themes.current(), EXPORT_CELL))
+        .collect();
+    if output.ends_with(".gif") {
+        let file = File::create(output).map_err(|err| format!("{}: {}", output, err))?;
+        let mut encoder = GifEncoder::new(BufWriter::new(file));
+        encoder
+            .set_repeat(Repeat::Infinite)
+            .and_then(|_| {
+                encoder.encode_frames(frames.iter().map(|frame| {
+                    Frame::from_parts(
+                        frame.clone(),
+                        0,
+                        0,
+                        Delay::from_numer_denom_ms(GIF_DELAY_MS, 1),
+                    )
+                }))
+            })
+            .map_err(|err| format!("{}: {}", output, err))?;
+    } else {
+        fs::create_dir_all(output).map_err(|err| format!("{}: {}", output, err))?;
+        for (i, frame) in frames.iter().enumerate() {
+            let path = Path::new(output).join(format!("frame-{:04}.png", i));
+            frame
+                .save(&path)
+                .map_err(|err| format!("{}: {}", path.display(), err))?;
+        }
+    }
+    println!("Exported {} frames to {}", frames.len(), output);
+    Ok(())
+}
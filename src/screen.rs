@@ -0,0 +1,57 @@
+use bevy::ecs::schedule::ShouldRun;
+use bevy::prelude::*;
+
+// 今表示している画面。ゲーム以外の画面を開いている間はスネークもタイマーも止める
+#[derive(Clone, Copy, PartialEq, Eq, Default)]
+pub enum Screen {
+    #[default]
+    Playing,
+    // 一時停止のメニュー
+    Paused,
+    // プレイのまとめを表示するゲームオーバーの画面
+    GameOver,
+    // メインメニュー
+    Menu,
+    // 設定の画面
+    Settings,
+    // キー割り当ての画面
+    Bindings,
+}
+
+// ゲームの画面を表示しているときだけ動かすSystemの実行条件
+pub fn playing(screen: Res<Screen>) -> ShouldRun {
+    if *screen == Screen::Playing {
+        ShouldRun::Yes
+    } else {
+        ShouldRun::No
+    }
+}
+
+// 一時停止中に1ティックだけ進める要求
+#[derive(Default)]
+pub struct SingleStep {
+    // 次のフレームで1ティック進める
+    pub requested: bool,
+    // このフレームで1ティック進めている
+    active: bool,
+}
+
+// フレームの始めに、要求があればこのフレームだけ1ティック進める
+// 移動とハザードとFoodの生成がどれも同じフレームで1回ずつ動くよう、実行条件では要求を消さない
+pub fn begin_step(mut step: ResMut<SingleStep>) {
+    step.active = std::mem::take(&mut step.requested);
+}
+
+// 固定タイムステップの実行条件の後ろにつなぎ、ゲームの画面以外では実行しない
+// ただし、一時停止中に1ティックだけ進めるフレームでは1回だけ実行する
+pub fn while_playing_or_stepping(
+    In(run): In<ShouldRun>,
+    screen: Res<Screen>,
+    step: Res<SingleStep>,
+) -> ShouldRun {
+    match *screen {
+        Screen::Playing => run,
+        Screen::Paused if step.active => ShouldRun::Yes,
+        _ => ShouldRun::No,
+    }
+}
@@ -0,0 +1,389 @@
+use std::fs;
+use std::marker::PhantomData;
+
+use bevy::ecs::system::SystemParam;
+use bevy::prelude::*;
+use bevy::window::{PresentMode, WindowMode};
+use serde::{Deserialize, Serialize};
+
+use crate::arena::{BorderWidth, WallMode};
+use crate::audio::Volumes;
+use crate::bindings::{BindingsMenu, ControlScheme, InputMap};
+use crate::effects::Effects;
+use crate::lives::Lives;
+use crate::screen::Screen;
+use crate::theme::Themes;
+use crate::tick::{self, Interpolation, TickTimer};
+use crate::widgets::{self, Focus, ScreenEvents, WidgetButton, WidgetStyle};
+
+// 設定を保存するファイル
+const SETTINGS_PATH: &str = "settings.ron";
+// 設定の一覧の枠の高さ（ピクセル）
+const LIST_HEIGHT: f32 = 640.0;
+
+// 設定の画面の行（上から順）
+const TICK: usize = 0;
+const LIVES: usize = 1;
+const BORDER_WIDTH: usize = 2;
+const WALLS: usize = 3;
+const THEME: usize = 4;
+const MASTER_VOLUME: usize = 5;
+const MUSIC_VOLUME: usize = 6;
+const SFX_VOLUME: usize = 7;
+const WINDOW: usize = 8;
+const VSYNC: usize = 9;
+const INTERPOLATION: usize = 10;
+const PARTICLES: usize = 11;
+const SHAKE: usize = 12;
+const DISSOLVE: usize = 13;
+const POPUPS: usize = 14;
+const BINDINGS: usize = 15;
+const BACK: usize = 16;
+
+// ウィンドウの表示方法
+#[derive(Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum DisplayMode {
+    #[default]
+    Windowed,
+    Borderless,
+    Fullscreen,
+}
+
+impl DisplayMode {
+    const ALL: [DisplayMode; 3] = [
+        DisplayMode::Windowed,
+        DisplayMode::Borderless,
+        DisplayMode::Fullscreen,
+    ];
+
+    pub fn window_mode(self) -> WindowMode {
+        match self {
+            DisplayMode::Windowed => WindowMode::Windowed,
+            DisplayMode::Borderless => WindowMode::BorderlessFullscreen,
+            DisplayMode::Fullscreen => WindowMode::Fullscreen,
+        }
+    }
+
+    fn from_window_mode(mode: WindowMode) -> Self {
+        match mode {
+            WindowMode::Windowed => DisplayMode::Windowed,
+            WindowMode::BorderlessFullscreen => DisplayMode::Borderless,
+            WindowMode::SizedFullscreen | WindowMode::Fullscreen => DisplayMode::Fullscreen,
+        }
+    }
+
+    fn label(self) -> &'static str {
+        match self {
+            DisplayMode::Windowed => "windowed",
+            DisplayMode::Borderless => "borderless",
+            DisplayMode::Fullscreen => "fullscreen",
+        }
+    }
+}
+
+// 垂直同期するかどうかを表示方法にする
+pub fn present_mode(vsync: bool) -> PresentMode {
+    if vsync {
+        PresentMode::Fifo
+    } else {
+        PresentMode::Immediate
+    }
+}
+
+// settings.ronに保存する設定（古いファイルに項目がなければ既定値を使う）
+#[derive(Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    pub bindings: InputMap,
+    pub control_scheme: ControlScheme,
+    // スネークが1マス動く間隔（ミリ秒）
+    pub tick_millis: u32,
+    // 残機の数
+    pub lives: u32,
+    pub border_width: BorderWidth,
+    pub wall_mode: WallMode,
+    // テーマの名前（なければ既定のテーマ）
+    pub theme: Option<String>,
+    pub volumes: Volumes,
+    pub display_mode: DisplayMode,
+    pub vsync: bool,
+    pub interpolation: bool,
+    // 演出ごとのオン・オフ
+    pub particles: bool,
+    pub shake: bool,
+    pub dissolve: bool,
+    pub popups: bool,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            bindings: InputMap::default(),
+            control_scheme: ControlScheme::default(),
+            tick_millis: 150,
+            lives: crate::SNAKE_LIVES,
+            border_width: BorderWidth::default(),
+            wall_mode: WallMode::default(),
+            theme: None,
+            volumes: Volumes::default(),
+            display_mode: DisplayMode::default(),
+            vsync: true,
+            interpolation: false,
+            particles: true,
+            shake: true,
+            dissolve: true,
+            popups: true,
+        }
+    }
+}
+
+impl Settings {
+    // ファイルから設定を読み込む。読み込めなければ既定値にする
+    pub fn load() -> Self {
+        match fs::read_to_string(SETTINGS_PATH) {
+            Ok(contents) => ron::from_str(&contents).unwrap_or_else(|err| {
+                warn!("Failed to read {}: {}", SETTINGS_PATH, err);
+                Self::default()
+            }),
+            Err(_) => Self::default(),
+        }
+    }
+
+    pub fn save(&self) {
+        let result = ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default())
+            .map_err(|err| err.to_string())
+            .and_then(|contents| fs::write(SETTINGS_PATH, contents).map_err(|err| err.to_string()));
+        if let Err(err) = result {
+            warn!("Failed to save settings: {}", err);
+        }
+    }
+}
+
+// allの中でcurrentからstepだけ進めた（戻した）値
+fn cycled<T: Copy + PartialEq>(all: &[T], current: T, step: i32) -> T {
+    let index = all.iter().position(|item| *item == current).unwrap_or(0) as i32;
+    all[(index + step).rem_euclid(all.len() as i32) as usize]
+}
+
+fn on_off(value: bool) -> &'static str {
+    if value {
+        "on"
+    } else {
+        "off"
+    }
+}
+
+// 設定の画面で変える値（すぐに反映できるものはリソースやウィンドウを直接変える）
+#[derive(SystemParam)]
+pub(crate) struct Options<'w, 's> {
+    timer: ResMut<'w, TickTimer>,
+    lives: ResMut<'w, Lives>,
+    border_width: ResMut<'w, BorderWidth>,
+    wall_mode: ResMut<'w, WallMode>,
+    themes: ResMut<'w, Themes>,
+    volumes: ResMut<'w, Volumes>,
+    interpolation: ResMut<'w, Interpolation>,
+    effects: ResMut<'w, Effects>,
+    windows: ResMut<'w, Windows>,
+    settings: ResMut<'w, Settings>,
+    #[system_param(ignore)]
+    marker: PhantomData<&'s ()>,
+}
+
+impl<'w, 's> Options<'w, 's> {
+    fn display_mode(&self) -> DisplayMode {
+        self.windows
+            .get_primary()
+            .map_or(DisplayMode::default(), |window| {
+                DisplayMode::from_window_mode(window.mode())
+            })
+    }
+
+    fn vsync(&self) -> bool {
+        matches!(self.windows.get_primary(), Some(window) if window.present_mode() != PresentMode::Immediate)
+    }
+
+    // 設定の画面の各行の文字
+    fn labels(&self) -> Vec<String> {
+        let percent = |volume: f32| (volume * 100.0).round() as u32;
+        vec![
+            format!("Tick speed: {} ms per move", self.timer.millis),
+            format!("Lives: {} (next run)", self.lives.max()),
+            format!("Border width: {} (next run)", self.border_width.label()),
+            format!("Walls: {}", self.wall_mode.label()),
+            format!("Theme: {}", self.themes.current().name),
+            format!("Master volume: {}%", percent(self.volumes.master)),
+            format!("Music volume: {}%", percent(self.volumes.music)),
+            format!("Effects volume: {}%", percent(self.volumes.sfx)),
+            format!("Window: {}", self.display_mode().label()),
+            format!("VSync: {}", on_off(self.vsync())),
+            format!("Interpolation: {}", on_off(self.interpolation.0)),
+            format!("Particles: {}", on_off(self.effects.particles)),
+            format!("Screen shake: {}", on_off(self.effects.shake)),
+            format!("Death dissolve: {}", on_off(self.effects.dissolve)),
+            format!("Score popups: {}", on_off(self.effects.popups)),
+            "Key bindings".to_string(),
+            "Back".to_string(),
+        ]
+    }
+
+    // rowの行の値をstepだけ変える（-1で前の値、+1で次の値）
+    fn step(&mut self, row: usize, step: i32) {
+        let volume = |volume: &mut f32| {
+            *volume = (((*volume * 10.0).round() + step as f32) / 10.0).clamp(0.0, 1.0);
+        };
+        match row {
+            TICK => {
+                let millis = self.timer.millis as i32 + step * tick::TICK_MILLIS_STEP as i32;
+                self.timer.millis =
+                    (millis.max(0) as u32).clamp(tick::MIN_TICK_MILLIS, tick::MAX_TICK_MILLIS);
+            }
+            LIVES => {
+                let lives = self.lives.max() as i32 + step;
+                self.lives.set_max(lives.max(0) as u32);
+            }
+            BORDER_WIDTH => {
+                *self.border_width = cycled(&BorderWidth::ALL, *self.border_width, step)
+            }
+            WALLS => *self.wall_mode = self.wall_mode.toggled(),
+            THEME => {
+                if step < 0 {
+                    self.themes.cycle_back();
+                } else {
+                    self.themes.cycle();
+                }
+            }
+            MASTER_VOLUME => volume(&mut self.volumes.master),
+            MUSIC_VOLUME => volume(&mut self.volumes.music),
+            SFX_VOLUME => volume(&mut self.volumes.sfx),
+            WINDOW => {
+                let mode = cycled(&DisplayMode::ALL, self.display_mode(), step);
+                if let Some(window) = self.windows.get_primary_mut() {
+                    window.set_mode(mode.window_mode());
+                }
+            }
+            VSYNC => {
+                let vsync = !self.vsync();
+                if let Some(window) = self.windows.get_primary_mut() {
+                    window.set_present_mode(present_mode(vsync));
+                }
+            }
+            INTERPOLATION => self.interpolation.0 = !self.interpolation.0,
+            PARTICLES => self.effects.particles = !self.effects.particles,
+            SHAKE => self.effects.shake = !self.effects.shake,
+            DISSOLVE => self.effects.dissolve = !self.effects.dissolve,
+            POPUPS => self.effects.popups = !self.effects.popups,
+            _ => {}
+        }
+    }
+
+    // 今の値を読み込んだ設定に書き込んで保存する
+    fn save(&mut self) {
+        let display_mode = self.display_mode();
+        let vsync = self.vsync();
+        let settings = &mut self.settings;
+        settings.tick_millis = self.timer.millis;
+        settings.lives = self.lives.max();
+        settings.border_width = *self.border_width;
+        settings.wall_mode = *self.wall_mode;
+        settings.theme = Some(self.themes.current().name.clone());
+        settings.volumes = *self.volumes;
+        settings.display_mode = display_mode;
+        settings.vsync = vsync;
+        settings.interpolation = self.interpolation.0;
+        settings.particles = self.effects.particles;
+        settings.shake = self.effects.shake;
+        settings.dissolve = self.effects.dissolve;
+        settings.popups = self.effects.popups;
+        settings.save();
+    }
+}
+
+// 設定の画面を閉じたときに戻る画面
+#[derive(Default)]
+pub struct SettingsMenu {
+    back: Screen,
+}
+
+impl SettingsMenu {
+    pub fn returning_to(back: Screen) -> Self {
+        Self { back }
+    }
+}
+
+// 設定の画面のUIの一番上のノード
+#[derive(Component)]
+pub struct SettingsRoot;
+
+// 設定の画面を開いたときとテーマを変えたときにUIを作り、開いている間は値の表示を更新する
+pub fn show_settings(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    screen: Res<Screen>,
+    options: Options,
+    mut focus: ResMut<Focus>,
+    roots: Query<Entity, With<SettingsRoot>>,
+    buttons: Query<(&WidgetButton, &Children)>,
+    mut texts: Query<&mut Text>,
+) {
+    let open = *screen == Screen::Settings;
+    if screen.is_changed() || options.themes.is_changed() {
+        for root in roots.iter() {
+            commands.entity(root).despawn_recursive();
+        }
+        if !open {
+            return;
+        }
+        let style = WidgetStyle::from_theme(options.themes.current(), &asset_server);
+        let labels = options.labels();
+        commands
+            .spawn_bundle(widgets::screen_root(&style))
+            .insert(SettingsRoot)
+            .with_children(|parent| {
+                widgets::title(parent, "SETTINGS", &style);
+                widgets::scrolling_list(parent, LIST_HEIGHT, |parent| {
+                    for (i, label) in labels.iter().enumerate() {
+                        widgets::button(parent, i, label, &style);
+                    }
+                });
+            });
+        focus.count = labels.len();
+        focus.index = focus.index.min(labels.len() - 1);
+    } else if open {
+        widgets::set_button_labels(&options.labels(), &buttons, &mut texts);
+    }
+}
+
+// 左右とEnterで値を変え、Escか「Back」で保存して前の画面に戻る
+pub fn settings_actions(
+    mut events: ScreenEvents,
+    mut screen: ResMut<Screen>,
+    settings_menu: Res<SettingsMenu>,
+    mut bindings_menu: ResMut<BindingsMenu>,
+    mut options: Options,
+) {
+    let input = match events.read(&screen, Screen::Settings) {
+        Some(input) => input,
+        None => return,
+    };
+    for (row, step) in input.steps {
+        options.step(row, step);
+    }
+    match input.activated {
+        Some(BINDINGS) => {
+            // キー割り当ての画面を開き、閉じたら設定の画面に戻る
+            *bindings_menu = BindingsMenu::returning_to(Screen::Settings);
+            *screen = Screen::Bindings;
+        }
+        Some(BACK) => {
+            options.save();
+            *screen = settings_menu.back;
+        }
+        Some(row) => options.step(row, 1),
+        None if input.back => {
+            options.save();
+            *screen = settings_menu.back;
+        }
+        None => {}
+    }
+}
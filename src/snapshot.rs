@@ -0,0 +1,600 @@
+use std::collections::{BTreeMap, HashSet};
+use std::fs;
+
+use bevy::ecs::system::SystemParam;
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::arena::Wall;
+use crate::hazard::{Hazard, HazardPath};
+use crate::level::{Level, Portal};
+use crate::{
+    spawn_segment, Direction, Food, Position, Size, SnakeHead, SnakeSegment, SnakeSegments,
+    ARENA_HEIGHT, ARENA_WIDTH,
+};
+
+// ある瞬間の盤面。ウィンドウやターミナルがなくても描画や保存ができる
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct Snapshot {
+    // 盤面の大きさ（マス数）
+    pub width: u32,
+    pub height: u32,
+    // ヘッドから尻尾の順に並んだスネークのセグメント
+    pub snake: Vec<Position>,
+    // ヘッドの向き
+    pub direction: Direction,
+    pub food: Vec<Position>,
+    pub walls: Vec<Position>,
+    pub hazards: Vec<Position>,
+    // ポータルのマスと、何番目の対か
+    pub portals: Vec<(Position, usize)>,
+    pub score: u32,
+}
+
+// ASCIIの盤面の文字
+const EMPTY: char = '.';
+const WALL: char = '#';
+const FOOD: char = '*';
+const HAZARD: char = 'x';
+const BODY: char = 'o';
+// 胴体の並びが盤面だけでは決まらないときに、盤面の後ろに書く行の頭
+const BODY_LINE: &str = "body ";
+// body の行がない盤面で、胴体の辿り方を探すときに進むマス数の上限
+const MAX_BODY_SEARCH: usize = 100_000;
+
+fn head_char(direction: Direction) -> char {
+    match direction {
+        Direction::Up => '^',
+        Direction::Right => '>',
+        Direction::Down => 'v',
+        Direction::Left => '<',
+    }
+}
+
+impl Snapshot {
+    // 盤面をASCIIの文字列にする
+    //
+    // 1行目は `score 点数`、続く行が盤面で、1行目が一番上（yが最大）の行になる。
+    // `.` は床、`#` は壁、`*` はFood、`x` はハザード、数字はポータル（何番目の対か）、
+    // `^` `>` `v` `<` は向きを表すスネークのヘッド、`o` はスネークの胴体。
+    // とぐろを巻いていて、ヘッドから分かれ道なしに胴体を辿れないときは、盤面の後ろに
+    // `body ` とヘッドから尻尾までの1マスごとの向き（`^` `>` `v` `<`）を書いた行を足す。
+    pub fn to_ascii(&self) -> String {
+        let mut grid = vec![vec![EMPTY; self.width as usize]; self.height as usize];
+        let mut put = |pos: &Position, c: char| {
+            if pos.x < self.width && pos.y < self.height {
+                grid[(self.height - 1 - pos.y) as usize][pos.x as usize] = c;
+            }
+        };
+        for pos in &self.walls {
+            put(pos, WALL);
+        }
+        for (pos, pair) in &self.portals {
+            put(pos, std::char::from_digit(*pair as u32 % 10, 10).unwrap());
+        }
+        for pos in &self.food {
+            put(pos, FOOD);
+        }
+        for pos in &self.hazards {
+            put(pos, HAZARD);
+        }
+        for (i, pos) in self.snake.iter().enumerate().rev() {
+            put(
+                pos,
+                if i == 0 {
+                    head_char(self.direction)
+                } else {
+                    BODY
+                },
+            );
+        }
+        let mut text = format!("score {}\n", self.score);
+        for row in grid {
+            text.extend(row);
+            text.push('\n');
+        }
+        if let Some(moves) = self.body_moves() {
+            text += &format!("{}{}\n", BODY_LINE, moves);
+        }
+        text
+    }
+
+    // 胴体の並びが盤面から1通りに決まらなければ、ヘッドから尻尾までの1マスごとの向きを返す
+    // 盤面を出力するたびに呼ぶので、全ての辿り方は探さずに、ヘッドから迷わずに辿れるかだけを見る
+    fn body_moves(&self) -> Option<String> {
+        let (head, body) = self.snake.split_first()?;
+        let cells: HashSet<Position> = body.iter().copied().collect();
+        if forced_body(*head, &cells).as_deref() == Some(&self.snake[..]) {
+            return None;
+        }
+        // ポータルをまたいでいる胴体は向きで書けない
+        self.snake
+            .windows(2)
+            .map(|pair| {
+                Direction::ALL
+                    .into_iter()
+                    .find(|dir| pair[0].step(*dir) == pair[1])
+                    .map(head_char)
+            })
+            .collect()
+    }
+
+    // to_asciiの形式の文字列から盤面を読み込む
+    // 各行の前後の空白と空行は無視するので、テストの中でインデントして書いてもよい
+    //
+    // 胴体の並びは、ヘッドから隣り合う `o` を全て1回ずつ通る順に辿って決める。
+    // 辿り方が2通り以上あるときは `body` の行に従い、その行がなければ読み込めない。
+    // とぐろが大きすぎて辿り方を探しきれないときも、`body` の行がなければ読み込めない。
+    // ポータルをまたいでいる胴体は辿れないので読み込めない。
+    pub fn from_ascii(text: &str) -> Result<Self, String> {
+        let mut lines = text.lines().map(str::trim).filter(|line| !line.is_empty());
+        let score = lines
+            .next()
+            .and_then(|line| line.strip_prefix("score "))
+            .ok_or("the first line must be `score <points>`")?;
+        let mut snapshot = Snapshot {
+            score: score
+                .trim()
+                .parse()
+                .map_err(|_| format!("invalid score '{}'", score))?,
+            ..default()
+        };
+        let mut rows: Vec<Vec<char>> = Vec::new();
+        let mut moves = None;
+        for line in lines {
+            match line.strip_prefix(BODY_LINE) {
+                Some(line) => moves = Some(line.trim()),
+                None => rows.push(line.chars().collect()),
+            }
+        }
+        snapshot.height = rows.len() as u32;
+        snapshot.width = rows.first().map_or(0, |row| row.len() as u32);
+        let mut head = None;
+        let mut body = HashSet::new();
+        for (row, tiles) in rows.iter().enumerate() {
+            if tiles.len() as u32 != snapshot.width {
+                return Err(format!(
+                    "row {} has {} columns, expected {}",
+                    row + 1,
+                    tiles.len(),
+                    snapshot.width
+                ));
+            }
+            for (column, tile) in tiles.iter().enumerate() {
+                let pos = Position {
+                    x: column as u32,
+                    y: snapshot.height - 1 - row as u32,
+                };
+                match *tile {
+                    EMPTY => {}
+                    WALL => snapshot.walls.push(pos),
+                    FOOD => snapshot.food.push(pos),
+                    HAZARD => snapshot.hazards.push(pos),
+                    BODY => {
+                        body.insert(pos);
+                    }
+                    c if c.is_ascii_digit() => snapshot
+                        .portals
+                        .push((pos, c.to_digit(10).unwrap() as usize)),
+                    c => match Direction::ALL.into_iter().find(|dir| head_char(*dir) == c) {
+                        Some(_) if head.is_some() => {
+                            return Err("the board has more than one head".to_string())
+                        }
+                        Some(direction) => head = Some((pos, direction)),
+                        None => return Err(format!("unknown tile '{}' at row {}", c, row + 1)),
+                    },
+                }
+            }
+        }
+        match head {
+            Some((pos, direction)) => {
+                snapshot.direction = direction;
+                snapshot.snake = match moves {
+                    Some(moves) => follow_body(pos, moves, &body)?,
+                    None => trace_body(pos, &body)?,
+                };
+            }
+            None if body.is_empty() => {}
+            None => return Err("the snake body has no head".to_string()),
+        }
+        Ok(snapshot)
+    }
+
+    // 盤面の壁とポータルとハザードからレベルを作る（ハザードはその場から動かない）
+    pub fn level(&self) -> Result<Level, String> {
+        if self.width != ARENA_WIDTH || self.height != ARENA_HEIGHT {
+            return Err(format!(
+                "the board is {}x{}, expected {}x{}",
+                self.width, self.height, ARENA_WIDTH, ARENA_HEIGHT
+            ));
+        }
+        let mut portal_ends: BTreeMap<usize, Vec<Position>> = BTreeMap::new();
+        for (pos, pair) in &self.portals {
+            portal_ends.entry(*pair).or_default().push(*pos);
+        }
+        let portals = portal_ends
+            .into_iter()
+            .map(|(pair, ends)| match ends[..] {
+                [a, b] => Ok((a, b)),
+                _ => Err(format!("portal {} must appear exactly twice", pair)),
+            })
+            .collect::<Result<Vec<_>, String>>()?;
+        let hazards = self
+            .hazards
+            .iter()
+            .map(|pos| HazardPath::new(&[*pos], false))
+            .collect::<Result<Vec<_>, String>>()?;
+        Ok(Level {
+            walls: self.walls.clone(),
+            portals,
+            hazards,
+        })
+    }
+}
+
+// コマンドライン引数の --board で指定されたファイルの、ゲームを始める盤面
+pub struct StartingBoard(pub Option<Snapshot>);
+
+impl StartingBoard {
+    pub fn from_args() -> Self {
+        let args: Vec<String> = std::env::args().collect();
+        let board = args
+            .iter()
+            .position(|arg| arg == "--board")
+            .and_then(|i| args.get(i + 1))
+            .and_then(|path| {
+                fs::read_to_string(path)
+                    .map_err(|err| err.to_string())
+                    .and_then(|text| Snapshot::from_ascii(&text))
+                    .map_err(|err| warn!("Failed to load board {}: {}", path, err))
+                    .ok()
+            });
+        Self(board)
+    }
+}
+
+// 盤面のスネークとFoodを生成する（壁とポータルとハザードはlevelから生成する）
+pub fn spawn_board(commands: &mut Commands, segments: &mut SnakeSegments, board: &Snapshot) {
+    segments.clear();
+    for (i, pos) in board.snake.iter().enumerate() {
+        let segment = if i == 0 {
+            commands
+                .spawn_bundle(SpriteBundle::default())
+                .insert(SnakeHead {
+                    direction: board.direction,
+                })
+                .insert(SnakeSegment)
+                .insert(*pos)
+                .insert(Size::square(0.8))
+                .id()
+        } else {
+            spawn_segment(commands, *pos)
+        };
+        segments.push(segment);
+    }
+    for pos in &board.food {
+        commands
+            .spawn_bundle(SpriteBundle::default())
+            .insert(Food)
+            .insert(*pos)
+            .insert(Size::square(0.8));
+    }
+}
+
+pub fn print_board(keyboard_input: Res<Input<KeyCode>>, board: Board) {
+    // Bキーで今の盤面をASCIIで出力する（バグ報告に貼り付けたり、--boardで読み込んだりできる）
+    if keyboard_input.just_pressed(KeyCode::B) {
+        println!("{}", board.snapshot().to_ascii());
+    }
+}
+
+// ヘッドから、次に進めるマスが1つしかない間だけ胴体を辿る
+// 全てのマスを辿れたら、それが胴体を1回ずつ通るただ1つの道になる
+fn forced_body(head: Position, body: &HashSet<Position>) -> Option<Vec<Position>> {
+    let mut path = vec![head];
+    let mut visited = HashSet::from([head]);
+    while path.len() <= body.len() {
+        let last = *path.last().unwrap();
+        let mut next = Direction::ALL
+            .into_iter()
+            .map(|dir| last.step(dir))
+            .filter(|pos| body.contains(pos) && !visited.contains(pos));
+        match (next.next(), next.next()) {
+            (Some(pos), None) => {
+                path.push(pos);
+                visited.insert(pos);
+            }
+            _ => return None,
+        }
+    }
+    Some(path)
+}
+
+// ヘッドから、胴体の全てのマスを1回ずつ通る道を最大limit本まで探す
+// 道の数はとぐろの大きさに対して指数的に増えるので、MAX_BODY_SEARCHマス進んだら諦めてNoneを返す
+fn body_paths(
+    head: Position,
+    body: &HashSet<Position>,
+    limit: usize,
+) -> Option<Vec<Vec<Position>>> {
+    fn walk(
+        path: &mut Vec<Position>,
+        body: &HashSet<Position>,
+        limit: usize,
+        budget: &mut usize,
+        found: &mut Vec<Vec<Position>>,
+    ) {
+        if path.len() == body.len() + 1 {
+            found.push(path.clone());
+            return;
+        }
+        let last = *path.last().unwrap();
+        for dir in Direction::ALL {
+            if found.len() >= limit || *budget == 0 {
+                return;
+            }
+            let next = last.step(dir);
+            if body.contains(&next) && !path.contains(&next) {
+                *budget -= 1;
+                path.push(next);
+                walk(path, body, limit, budget, found);
+                path.pop();
+            }
+        }
+    }
+    let mut found = Vec::new();
+    let mut budget = MAX_BODY_SEARCH;
+    walk(&mut vec![head], body, limit, &mut budget, &mut found);
+    if budget == 0 && found.len() < limit {
+        return None;
+    }
+    Some(found)
+}
+
+// 胴体を辿る道が1本だけならそれを使う。2本以上あれば、どちらが尻尾か分からないので読み込まない
+fn trace_body(head: Position, body: &HashSet<Position>) -> Result<Vec<Position>, String> {
+    if let Some(path) = forced_body(head, body) {
+        return Ok(path);
+    }
+    let mut paths = body_paths(head, body, 2)
+        .ok_or("the snake body is too coiled to read, add a `body` line")?;
+    match paths.len() {
+        0 => Err("the snake body is not one connected line from the head".to_string()),
+        1 => Ok(paths.remove(0)),
+        _ => {
+            Err("the snake body can be read in more than one order, add a `body` line".to_string())
+        }
+    }
+}
+
+// `body` の行の向きに従って、ヘッドから胴体を辿る
+fn follow_body(
+    head: Position,
+    moves: &str,
+    body: &HashSet<Position>,
+) -> Result<Vec<Position>, String> {
+    let mut path = vec![head];
+    for c in moves.chars() {
+        let dir = Direction::ALL
+            .into_iter()
+            .find(|dir| head_char(*dir) == c)
+            .ok_or_else(|| format!("unknown move '{}' in the body line", c))?;
+        let next = path.last().unwrap().step(dir);
+        if !body.contains(&next) || path.contains(&next) {
+            return Err(format!("the body line leaves the body at '{}'", c));
+        }
+        path.push(next);
+    }
+    if path.len() != body.len() + 1 {
+        return Err("the body line does not cover the whole body".to_string());
+    }
+    Ok(path)
+}
+
+// to_asciiの結果が期待する盤面と一致することを確かめる
+// 期待する盤面は各行の前後の空白と空行を無視して比べる
+#[cfg(test)]
+macro_rules! assert_board {
+    ($state:expr, $expected:expr) => {{
+        let actual = $state.to_ascii();
+        let expected: String = $expected
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+            .map(|line| format!("{}\n", line))
+            .collect();
+        assert!(
+            actual == expected,
+            "boards differ\n--- actual ---\n{}--- expected ---\n{}",
+            actual,
+            expected
+        );
+    }};
+}
+#[cfg(test)]
+pub(crate) use assert_board;
+
+// 盤面を読むためのリソースとクエリをまとめたSystemParam
+#[derive(SystemParam)]
+pub(crate) struct Board<'w, 's> {
+    score: Res<'w, u32>,
+    segments: Res<'w, SnakeSegments>,
+    positions: Query<'w, 's, &'static Position>,
+    heads: Query<'w, 's, &'static SnakeHead>,
+    food: Query<'w, 's, &'static Position, With<Food>>,
+    walls: Query<'w, 's, &'static Position, With<Wall>>,
+    hazards: Query<'w, 's, &'static Position, With<Hazard>>,
+    portals: Query<'w, 's, (&'static Position, &'static Portal)>,
+}
+
+impl<'w, 's> Board<'w, 's> {
+    pub fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            width: ARENA_WIDTH,
+            height: ARENA_HEIGHT,
+            // このティックで成長したばかりのセグメントはまだ位置を持たないので含めない
+            snake: self
+                .segments
+                .iter()
+                .filter_map(|e| self.positions.get(*e).ok().copied())
+                .collect(),
+            direction: self
+                .segments
+                .first()
+                .and_then(|e| self.heads.get(*e).ok())
+                .map(|head| head.direction)
+                .unwrap_or_default(),
+            food: self.food.iter().copied().collect(),
+            walls: self.walls.iter().copied().collect(),
+            hazards: self.hazards.iter().copied().collect(),
+            portals: self
+                .portals
+                .iter()
+                .map(|(pos, portal)| (*pos, portal.pair))
+                .collect(),
+            score: *self.score,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trips_a_board() {
+        let board = "
+            score 12
+            #######
+            #..*..#
+            #.ooo.#
+            #.o.<.#
+            #.oo.x#
+            #0...0#
+            #######
+        ";
+        let snapshot = Snapshot::from_ascii(board).unwrap();
+        assert_board!(snapshot, board);
+    }
+
+    #[test]
+    fn reads_the_body_in_order_from_the_head() {
+        let snapshot = Snapshot::from_ascii(
+            "
+            score 0
+            ....
+            .>o.
+            ..o.
+            .oo.
+            ",
+        )
+        .unwrap();
+        assert_eq!(snapshot.width, 4);
+        assert_eq!(snapshot.height, 4);
+        assert!(snapshot.direction == Direction::Right);
+        let cells: Vec<(u32, u32)> = snapshot.snake.iter().map(|pos| (pos.x, pos.y)).collect();
+        assert_eq!(cells, [(1, 2), (2, 2), (2, 1), (2, 0), (1, 0)]);
+    }
+
+    #[test]
+    fn needs_a_body_line_for_a_coiled_snake() {
+        // ヘッドから時計回りにも反時計回りにも辿れるので、尻尾がどちらの端か分からない
+        let coiled = "
+            score 0
+            >o
+            oo
+        ";
+        assert!(Snapshot::from_ascii(coiled).is_err());
+        let with_order = "
+            score 0
+            >o
+            oo
+            body >v<
+        ";
+        let snapshot = Snapshot::from_ascii(with_order).unwrap();
+        let cells: Vec<(u32, u32)> = snapshot.snake.iter().map(|pos| (pos.x, pos.y)).collect();
+        assert_eq!(cells, [(0, 1), (1, 1), (1, 0), (0, 0)]);
+        // 書き出すときも並びを残す
+        assert_board!(snapshot, with_order);
+    }
+
+    #[test]
+    fn writes_the_body_line_for_a_long_coil_without_searching() {
+        // 12x12の盤面を往復して埋めるスネーク。辿り方を全て探すと終わらない大きさ
+        let size = 12;
+        let snake: Vec<Position> = (0..size)
+            .flat_map(|y| {
+                (0..size).map(move |i| Position {
+                    x: if y % 2 == 0 { i } else { size - 1 - i },
+                    y,
+                })
+            })
+            .collect();
+        let snapshot = Snapshot {
+            width: size,
+            height: size,
+            snake,
+            direction: Direction::Left,
+            ..default()
+        };
+        let text = snapshot.to_ascii();
+        assert!(text.contains(BODY_LINE));
+        let read = Snapshot::from_ascii(&text).unwrap();
+        assert!(read.snake == snapshot.snake);
+        // body の行がなければ、探すのを途中で諦めて読み込まない
+        let without_body: String = text
+            .lines()
+            .filter(|line| !line.starts_with(BODY_LINE))
+            .map(|line| format!("{}\n", line))
+            .collect();
+        assert!(Snapshot::from_ascii(&without_body).is_err());
+    }
+
+    #[test]
+    fn places_the_first_row_at_the_top() {
+        let snapshot = Snapshot::from_ascii("score 0\n*..\n...\n..#").unwrap();
+        assert!(snapshot.food == [Position { x: 0, y: 2 }]);
+        assert!(snapshot.walls == [Position { x: 2, y: 0 }]);
+    }
+
+    #[test]
+    fn draws_a_snapshot_taken_by_hand() {
+        let snapshot = Snapshot {
+            width: 5,
+            height: 3,
+            snake: vec![Position { x: 1, y: 1 }, Position { x: 2, y: 1 }],
+            direction: Direction::Left,
+            food: vec![Position { x: 4, y: 2 }],
+            portals: vec![(Position { x: 0, y: 0 }, 1)],
+            score: 3,
+            ..default()
+        };
+        assert_board!(
+            snapshot,
+            "
+            score 3
+            ....*
+            .<o..
+            1....
+            "
+        );
+    }
+
+    #[test]
+    fn rejects_broken_boards() {
+        let errors = [
+            "....",
+            "score x\n...",
+            "score 0\n...\n..",
+            "score 0\n.?.",
+            "score 0\n>.<",
+            "score 0\n.o.",
+            "score 0\n>.o",
+            "score 0\n>o\noo\nbody >>",
+            "score 0\n>o\noo\nbody >v",
+        ];
+        for board in errors {
+            assert!(Snapshot::from_ascii(board).is_err(), "accepted {:?}", board);
+        }
+    }
+}
@@ -0,0 +1,198 @@
+use bevy::asset::LoadState;
+use bevy::prelude::*;
+use bevy::render::texture::DEFAULT_IMAGE_HANDLE;
+
+use crate::theme::Themes;
+use crate::{Direction, Food, Position, SnakeHead, SnakeSegment, SnakeSegments};
+
+// スプライトシートは32x32のタイルが4x4で並ぶ
+const TILE_SIZE: f32 = 32.0;
+const SHEET_COLUMNS: usize = 4;
+const SHEET_ROWS: usize = 4;
+
+// スプライトシート上のタイルの番号
+// ヘッド、コーナー、尻尾は上・右・下・左の順に4方向分が並ぶ
+const HEAD: usize = 0;
+const BODY_VERTICAL: usize = 4;
+const BODY_HORIZONTAL: usize = 5;
+const FOOD: usize = 6;
+// 上と右、右と下、下と左、左と上をつなぐコーナー
+const CORNER: usize = 8;
+// 前のセグメントが上・右・下・左にある尻尾
+const TAIL: usize = 12;
+
+// 今のテーマのスプライトシート
+#[derive(Default)]
+pub struct SnakeSpriteSheet {
+    loaded: Option<LoadedSheet>,
+}
+
+struct LoadedSheet {
+    path: String,
+    image: Handle<Image>,
+    atlas: Handle<TextureAtlas>,
+    failed: bool,
+}
+
+pub fn apply_sprite_sheet(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    themes: Res<Themes>,
+    mut atlases: ResMut<Assets<TextureAtlas>>,
+    mut sheet: ResMut<SnakeSpriteSheet>,
+    flat: Query<Entity, (With<Sprite>, Or<(With<SnakeSegment>, With<Food>)>)>,
+    textured: Query<Entity, With<TextureAtlasSprite>>,
+) {
+    // テーマのスプライトシートが変わったら読み込み直す
+    let wanted = themes.current().sprite_sheet.as_deref();
+    if sheet.loaded.as_ref().map(|loaded| loaded.path.as_str()) != wanted {
+        sheet.loaded = wanted.map(|path| {
+            let image = asset_server.load(path);
+            let atlas = atlases.add(TextureAtlas::from_grid(
+                image.clone(),
+                Vec2::splat(TILE_SIZE),
+                SHEET_COLUMNS,
+                SHEET_ROWS,
+            ));
+            LoadedSheet {
+                path: path.to_string(),
+                image,
+                atlas,
+                failed: false,
+            }
+        });
+    }
+    let atlas = match sheet.loaded.as_mut() {
+        Some(loaded) if !loaded.failed => match asset_server.get_load_state(&loaded.image) {
+            LoadState::Loaded => Some(loaded.atlas.clone()),
+            // 画像が読み込めなければ単色の四角形で描画する
+            LoadState::Failed => {
+                warn!("Failed to load {}, falling back to flat colors", loaded.path);
+                loaded.failed = true;
+                None
+            }
+            // 読み込み中は今の描画のままにする
+            _ => return,
+        },
+        _ => None,
+    };
+    match atlas {
+        // 単色のスネークとFoodをスプライトシートの描画に差し替える
+        Some(atlas) => {
+            for entity in flat.iter() {
+                commands
+                    .entity(entity)
+                    .remove::<Sprite>()
+                    .remove::<Handle<Image>>()
+                    .insert(TextureAtlasSprite {
+                        // size_scalingのスケールがそのままマスの大きさになるように1x1にする
+                        custom_size: Some(Vec2::ONE),
+                        ..default()
+                    })
+                    .insert(atlas.clone());
+            }
+        }
+        // スプライトシートを使わないテーマでは単色の四角形に戻す（色はapply_themeで塗る）
+        None => {
+            for entity in textured.iter() {
+                commands
+                    .entity(entity)
+                    .remove::<TextureAtlasSprite>()
+                    .remove::<Handle<TextureAtlas>>()
+                    .insert(Sprite::default())
+                    .insert(DEFAULT_IMAGE_HANDLE.typed::<Image>());
+            }
+        }
+    }
+}
+
+// fromからtoへの方向（隣り合っていなければNone）
+fn direction_between(from: &Position, to: &Position) -> Option<Direction> {
+    if from.y == to.y && from.x + 1 == to.x {
+        Some(Direction::Right)
+    } else if from.y == to.y && to.x + 1 == from.x {
+        Some(Direction::Left)
+    } else if from.x == to.x && from.y + 1 == to.y {
+        Some(Direction::Up)
+    } else if from.x == to.x && to.y + 1 == from.y {
+        Some(Direction::Down)
+    } else {
+        None
+    }
+}
+
+// 上・右・下・左の順の番号
+fn direction_offset(direction: Direction) -> usize {
+    match direction {
+        Direction::Up => 0,
+        Direction::Right => 1,
+        Direction::Down => 2,
+        Direction::Left => 3,
+    }
+}
+
+fn straight(direction: Direction) -> usize {
+    match direction {
+        Direction::Up | Direction::Down => BODY_VERTICAL,
+        Direction::Left | Direction::Right => BODY_HORIZONTAL,
+    }
+}
+
+// 前後のセグメントへの方向から胴体のタイルを選ぶ
+fn body_tile(towards_head: Option<Direction>, towards_tail: Option<Direction>) -> usize {
+    match (towards_head, towards_tail) {
+        (Some(a), Some(b)) if a == b.opposite() => straight(a),
+        (Some(a), Some(b)) => {
+            // 時計回りに並べたときに先に来る方向でコーナーを決める
+            let (a, b) = (direction_offset(a), direction_offset(b));
+            let first = if (a + 1) % 4 == b { a } else { b };
+            CORNER + first
+        }
+        // ポータルをまたいでいる場合は、つながっている側に合わせてまっすぐにする
+        (Some(a), None) | (None, Some(a)) => straight(a),
+        (None, None) => BODY_VERTICAL,
+    }
+}
+
+pub fn update_snake_sprites(
+    segments: Res<SnakeSegments>,
+    heads: Query<&SnakeHead>,
+    positions: Query<&Position, With<SnakeSegment>>,
+    mut sprites: Query<&mut TextureAtlasSprite, Without<Food>>,
+    mut food_sprites: Query<&mut TextureAtlasSprite, With<Food>>,
+) {
+    // 前後のセグメントとの位置関係から、各セグメントのタイルを選ぶ
+    let segment_positions: Vec<Option<&Position>> =
+        segments.iter().map(|e| positions.get(*e).ok()).collect();
+    let neighbour = |i: usize, j: Option<usize>| match (segment_positions[i], j) {
+        (Some(pos), Some(j)) => segment_positions
+            .get(j)
+            .copied()
+            .flatten()
+            .and_then(|other| direction_between(pos, other)),
+        _ => None,
+    };
+    let last = segments.len().saturating_sub(1);
+    for (i, entity) in segments.iter().enumerate() {
+        let mut sprite = match sprites.get_mut(*entity) {
+            Ok(sprite) => sprite,
+            Err(_) => continue,
+        };
+        let towards_head = neighbour(i, i.checked_sub(1));
+        let towards_tail = neighbour(i, Some(i + 1));
+        sprite.index = if i == 0 {
+            let direction = heads
+                .get(*entity)
+                .map(|head| head.direction)
+                .unwrap_or(Direction::Up);
+            HEAD + direction_offset(direction)
+        } else if i == last {
+            TAIL + towards_head.map(direction_offset).unwrap_or(0)
+        } else {
+            body_tile(towards_head, towards_tail)
+        };
+    }
+    for mut sprite in food_sprites.iter_mut() {
+        sprite.index = FOOD;
+    }
+}
@@ -0,0 +1,279 @@
+use std::collections::VecDeque;
+
+use bevy::prelude::*;
+use bevy::render::render_resource::{Extent3d, TextureDimension, TextureFormat};
+
+use crate::lives::Lives;
+use crate::modes::{GameMode, RunClock, RunOverEvent};
+use crate::raster;
+use crate::screen::Screen;
+use crate::snapshot::{Board, Snapshot};
+use crate::theme::Themes;
+use crate::tick::TickTimer;
+use crate::widgets::{self, Focus, ScreenEvents, WidgetStyle};
+use crate::{DeathCause, GameOverEvent, GrowthEvent, SnakeSegments};
+
+// ミニリプレイで見せる、死ぬ前のティック数
+const REPLAY_TICKS: usize = 24;
+// ミニリプレイの1コマの長さ（秒）と、1マスの大きさ（ピクセル）
+const REPLAY_FRAME_TIME: f32 = 0.15;
+const REPLAY_CELL: u32 = 8;
+
+// ゲームオーバーの画面のボタン（上から順）
+const PLAY_AGAIN: usize = 0;
+const MAIN_MENU: usize = 1;
+
+// 今のプレイで食べたFoodの数と、一番速かったティックの長さ（ミリ秒）
+#[derive(Default)]
+pub struct RunStats {
+    pub food_eaten: u32,
+    pub fastest_tick: Option<u32>,
+}
+
+pub fn track_run_stats(
+    mut growth_reader: EventReader<GrowthEvent>,
+    timer: Res<TickTimer>,
+    mut stats: ResMut<RunStats>,
+) {
+    stats.food_eaten += growth_reader.iter().count() as u32;
+    let fastest = stats
+        .fastest_tick
+        .map_or(timer.millis, |fastest| fastest.min(timer.millis));
+    stats.fastest_tick = Some(fastest);
+}
+
+// 直近のティックの盤面（ミニリプレイに使う）
+#[derive(Default)]
+pub struct RecentBoards(VecDeque<Snapshot>);
+
+impl RecentBoards {
+    // プレイを始め直したときやリスポーンしたときに、前のスネークの盤面を忘れる
+    pub fn clear(&mut self) {
+        self.0.clear();
+    }
+}
+
+pub fn record_recent_boards(
+    mut recent: ResMut<RecentBoards>,
+    clock: Res<RunClock>,
+    mut last_tick: Local<Option<u32>>,
+    board: Board,
+) {
+    // スネークが動いたティックごとに盤面を覚え、古いものから捨てる
+    if *last_tick == Some(clock.ticks) {
+        return;
+    }
+    *last_tick = Some(clock.ticks);
+    recent.0.push_back(board.snapshot());
+    if recent.0.len() > REPLAY_TICKS {
+        recent.0.pop_front();
+    }
+}
+
+// プレイが終わった理由
+#[derive(Clone, Copy)]
+enum RunEnd {
+    Died(DeathCause),
+    // タイムアタックの制限時間を過ぎた
+    TimeUp,
+    // 目標の長さに到達した
+    TargetReached,
+}
+
+impl RunEnd {
+    fn label(self) -> &'static str {
+        match self {
+            RunEnd::Died(cause) => cause.label(),
+            RunEnd::TimeUp => "Time up",
+            RunEnd::TargetReached => "Target reached",
+        }
+    }
+}
+
+// 終わったプレイのまとめ
+pub struct Summary {
+    end: RunEnd,
+    mode: GameMode,
+    score: u32,
+    length: usize,
+    seconds: f32,
+    food_eaten: u32,
+    // 1秒あたりに進んだマス数の最大
+    top_speed: f32,
+    frames: Vec<Snapshot>,
+}
+
+impl Summary {
+    fn lines(&self) -> Vec<String> {
+        let seconds = self.seconds as u32;
+        vec![
+            format!("{} ({})", self.end.label(), self.mode),
+            format!("Score: {}", self.score),
+            format!("Length: {}", self.length),
+            format!("Time alive: {}:{:02}", seconds / 60, seconds % 60),
+            format!("Food eaten: {}", self.food_eaten),
+            format!("Top speed: {:.1} moves/s", self.top_speed),
+        ]
+    }
+}
+
+// 最後に終わったプレイのまとめ
+#[derive(Default)]
+pub struct LastRun(Option<Summary>);
+
+// プレイが終わったら（最後の残機を失うか、モードの終了条件を満たしたら）
+// リセットされる前に記録をまとめてゲームオーバーの画面を開く
+pub fn summarize_run(
+    mut run_over_reader: EventReader<RunOverEvent>,
+    mut game_over_reader: EventReader<GameOverEvent>,
+    lives: Res<Lives>,
+    mode: Res<GameMode>,
+    score: Res<u32>,
+    segments: Res<SnakeSegments>,
+    clock: Res<RunClock>,
+    stats: Res<RunStats>,
+    mut recent: ResMut<RecentBoards>,
+    mut last_run: ResMut<LastRun>,
+    mut screen: ResMut<Screen>,
+    board: Board,
+) {
+    let death = game_over_reader.iter().last().map(|GameOverEvent(cause)| *cause);
+    if run_over_reader.iter().next().is_none() {
+        return;
+    }
+    let end = match (death, *mode) {
+        (Some(cause), _) if lives.remaining == 0 => RunEnd::Died(cause),
+        (_, GameMode::TimeAttack) => RunEnd::TimeUp,
+        (_, GameMode::TargetLength) => RunEnd::TargetReached,
+        _ => return,
+    };
+    // 終わった瞬間の盤面（死んだときはぶつかったヘッド）までをミニリプレイにする
+    let mut frames: Vec<Snapshot> = recent.0.drain(..).collect();
+    frames.push(board.snapshot());
+    last_run.0 = Some(Summary {
+        end,
+        mode: *mode,
+        score: *score,
+        length: segments.len(),
+        seconds: clock.elapsed.elapsed_secs(),
+        food_eaten: stats.food_eaten,
+        top_speed: stats
+            .fastest_tick
+            .map_or(0.0, |millis| 1000.0 / millis as f32),
+        frames,
+    });
+    *screen = Screen::GameOver;
+}
+
+// ミニリプレイの画像と、今表示しているコマ
+#[derive(Component)]
+pub struct ReplayPlayer {
+    frames: Vec<Handle<Image>>,
+    index: usize,
+    timer: Timer,
+}
+
+// ゲームオーバーの画面のUIの一番上のノード
+#[derive(Component)]
+pub struct SummaryRoot;
+
+// 盤面をUIに表示できる画像にする
+fn frame_image(snapshot: &Snapshot, themes: &Themes) -> Image {
+    let image = raster::render(snapshot, themes.current(), REPLAY_CELL);
+    let (width, height) = image.dimensions();
+    Image::new(
+        Extent3d {
+            width,
+            height,
+            depth_or_array_layers: 1,
+        },
+        TextureDimension::D2,
+        image.into_raw(),
+        TextureFormat::Rgba8UnormSrgb,
+    )
+}
+
+// ゲームオーバーの画面を開いたら、記録とミニリプレイとボタンを表示する
+pub fn show_summary(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    themes: Res<Themes>,
+    screen: Res<Screen>,
+    last_run: Res<LastRun>,
+    mut images: ResMut<Assets<Image>>,
+    mut focus: ResMut<Focus>,
+    roots: Query<Entity, With<SummaryRoot>>,
+) {
+    if !screen.is_changed() {
+        return;
+    }
+    for root in roots.iter() {
+        commands.entity(root).despawn_recursive();
+    }
+    let summary = match (&*screen, &last_run.0) {
+        (Screen::GameOver, Some(summary)) => summary,
+        _ => return,
+    };
+
+    let style = WidgetStyle::from_theme(themes.current(), &asset_server);
+    let frames: Vec<Handle<Image>> = summary
+        .frames
+        .iter()
+        .map(|frame| images.add(frame_image(frame, &themes)))
+        .collect();
+    let extent = images.get(&frames[0]).unwrap().texture_descriptor.size;
+    let size = Size::new(Val::Px(extent.width as f32), Val::Px(extent.height as f32));
+    commands
+        .spawn_bundle(widgets::screen_root(&style))
+        .insert(SummaryRoot)
+        .with_children(|parent| {
+            widgets::title(parent, "GAME OVER", &style);
+            for line in summary.lines() {
+                widgets::label(parent, &line, &style);
+            }
+            parent
+                .spawn_bundle(ImageBundle {
+                    style: Style {
+                        size,
+                        flex_shrink: 0.0,
+                        margin: Rect::all(Val::Px(10.0)),
+                        ..default()
+                    },
+                    image: UiImage(frames[0].clone()),
+                    ..default()
+                })
+                .insert(ReplayPlayer {
+                    frames,
+                    index: 0,
+                    timer: Timer::from_seconds(REPLAY_FRAME_TIME, true),
+                });
+            widgets::button(parent, PLAY_AGAIN, "Play again", &style);
+            widgets::button(parent, MAIN_MENU, "Main menu", &style);
+        });
+    focus.count = 2;
+}
+
+// ミニリプレイのコマを順に送り、最後まで行ったら最初に戻る
+pub fn play_replay(time: Res<Time>, mut players: Query<(&mut ReplayPlayer, &mut UiImage)>) {
+    for (mut player, mut image) in players.iter_mut() {
+        if player.timer.tick(time.delta()).just_finished() {
+            player.index = (player.index + 1) % player.frames.len();
+            image.0 = player.frames[player.index].clone();
+        }
+    }
+}
+
+// もう一度遊ぶか、メインメニューに戻る（次のプレイはリセット済みで、画面を閉じると始まる）
+pub fn summary_actions(mut events: ScreenEvents, mut screen: ResMut<Screen>) {
+    let input = match events.read(&screen, Screen::GameOver) {
+        Some(input) => input,
+        None => return,
+    };
+    match input.activated {
+        Some(PLAY_AGAIN) => *screen = Screen::Playing,
+        Some(MAIN_MENU) => *screen = Screen::Menu,
+        Some(_) => {}
+        None if input.back => *screen = Screen::Menu,
+        None => {}
+    }
+}
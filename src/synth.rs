@@ -0,0 +1,230 @@
+use std::io::{self, Write};
+
+// 昔のゲーム機の音源のような、矩形波・三角波・ノイズの小さなシンセサイザー
+
+// 音の波形
+#[derive(Clone, Copy)]
+pub enum Wave {
+    // デューティ比（0.0〜1.0）を指定した矩形波
+    Square(f32),
+    Triangle,
+    // 15ビットのLFSRで作るノイズ。周波数はLFSRを進める速さになる
+    Noise,
+}
+
+// ADSRエンベロープ（時間は秒、sustainは音量の割合）
+#[derive(Clone, Copy)]
+pub struct Envelope {
+    pub attack: f32,
+    pub decay: f32,
+    pub sustain: f32,
+    pub release: f32,
+}
+
+impl Envelope {
+    // 鳴り始めてからt秒後の音量。hold秒で鍵盤を離したものとして減衰させる
+    fn level(&self, t: f32, hold: f32) -> f32 {
+        let held = |t: f32| {
+            if t < self.attack {
+                t / self.attack
+            } else if t < self.attack + self.decay {
+                1.0 - (1.0 - self.sustain) * (t - self.attack) / self.decay
+            } else {
+                self.sustain
+            }
+        };
+        if t < hold {
+            held(t)
+        } else if t < hold + self.release {
+            held(hold) * (1.0 - (t - hold) / self.release)
+        } else {
+            0.0
+        }
+    }
+}
+
+// 1つの音。start秒から鳴り始め、length秒の間に周波数がfromからtoへ変わる
+#[derive(Clone, Copy)]
+pub struct Note {
+    pub wave: Wave,
+    pub envelope: Envelope,
+    pub start: f32,
+    pub length: f32,
+    pub from: f32,
+    pub to: f32,
+    pub volume: f32,
+}
+
+impl Note {
+    // 周波数が変わらない音
+    pub fn new(wave: Wave, envelope: Envelope, start: f32, length: f32, frequency: f32) -> Self {
+        Self {
+            wave,
+            envelope,
+            start,
+            length,
+            from: frequency,
+            to: frequency,
+            volume: 1.0,
+        }
+    }
+
+    // 周波数をtoまで変化させる
+    pub fn sweep(self, to: f32) -> Self {
+        Self { to, ..self }
+    }
+
+    pub fn volume(self, volume: f32) -> Self {
+        Self { volume, ..self }
+    }
+
+    fn end(&self) -> f32 {
+        self.start + self.length + self.envelope.release
+    }
+
+    // outに足し込む
+    fn render_into(&self, out: &mut [f32], rate: u32) {
+        let first = (self.start * rate as f32) as usize;
+        let count = ((self.length + self.envelope.release) * rate as f32) as usize;
+        let mut phase = 0.0;
+        let mut lfsr: u16 = 1;
+        for (i, sample) in out.iter_mut().skip(first).take(count).enumerate() {
+            let t = i as f32 / rate as f32;
+            // 周波数は指数的に変える（音程が一定の速さで変わって聞こえる）
+            let progress = (t / self.length).min(1.0);
+            let frequency = self.from * (self.to / self.from).powf(progress);
+            phase += frequency / rate as f32;
+            let wrapped = phase >= 1.0;
+            phase = phase.fract();
+            let value = match self.wave {
+                Wave::Square(duty) => {
+                    if phase < duty {
+                        1.0
+                    } else {
+                        -1.0
+                    }
+                }
+                Wave::Triangle => 1.0 - 4.0 * (phase - 0.5).abs(),
+                Wave::Noise => {
+                    if wrapped {
+                        let bit = (lfsr ^ (lfsr >> 1)) & 1;
+                        lfsr = (lfsr >> 1) | (bit << 14);
+                    }
+                    if lfsr & 1 == 1 {
+                        1.0
+                    } else {
+                        -1.0
+                    }
+                }
+            };
+            *sample += value * self.volume * self.envelope.level(t, self.length);
+        }
+    }
+}
+
+// MIDIのノート番号の周波数（69がA4 = 440Hz）
+pub fn midi(note: u8) -> f32 {
+    440.0 * 2f32.powf((note as f32 - 69.0) / 12.0)
+}
+
+// 全ての音を混ぜて、最後の音が消えるまでの波形を作る
+pub fn render(notes: &[Note], rate: u32) -> Vec<f32> {
+    let end = notes.iter().map(Note::end).fold(0.0, f32::max);
+    let mut out = vec![0.0; (end * rate as f32).ceil() as usize];
+    for note in notes {
+        note.render_into(&mut out, rate);
+    }
+    out
+}
+
+// 16ビットモノラルのWAVファイルに書き出す
+pub fn write_wav(out: &mut impl Write, samples: &[f32], rate: u32) -> io::Result<()> {
+    let data_size = samples.len() as u32 * 2;
+    out.write_all(b"RIFF")?;
+    out.write_all(&(36 + data_size).to_le_bytes())?;
+    out.write_all(b"WAVEfmt ")?;
+    // PCM, 1チャンネル, サンプリングレート, 1秒のバイト数, 1サンプルのバイト数, ビット数
+    out.write_all(&16u32.to_le_bytes())?;
+    out.write_all(&1u16.to_le_bytes())?;
+    out.write_all(&1u16.to_le_bytes())?;
+    out.write_all(&rate.to_le_bytes())?;
+    out.write_all(&(rate * 2).to_le_bytes())?;
+    out.write_all(&2u16.to_le_bytes())?;
+    out.write_all(&16u16.to_le_bytes())?;
+    out.write_all(b"data")?;
+    out.write_all(&data_size.to_le_bytes())?;
+    for sample in samples {
+        let value = (sample.clamp(-1.0, 1.0) * i16::MAX as f32) as i16;
+        out.write_all(&value.to_le_bytes())?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ENVELOPE: Envelope = Envelope {
+        attack: 0.01,
+        decay: 0.02,
+        sustain: 0.5,
+        release: 0.04,
+    };
+
+    #[test]
+    fn writes_a_wav_header() {
+        let mut wav = Vec::new();
+        write_wav(&mut wav, &[0.0, 1.0, -1.0, 0.5], 22050).unwrap();
+        let u16_at = |i: usize| u16::from_le_bytes([wav[i], wav[i + 1]]);
+        let u32_at = |i: usize| u32::from_le_bytes([wav[i], wav[i + 1], wav[i + 2], wav[i + 3]]);
+        assert_eq!(wav.len(), 44 + 4 * 2);
+        assert_eq!(&wav[0..4], b"RIFF");
+        // RIFFの大きさは、先頭の8バイトを除いたファイルの大きさ
+        assert_eq!(u32_at(4) as usize, wav.len() - 8);
+        assert_eq!(&wav[8..16], b"WAVEfmt ");
+        assert_eq!(u32_at(16), 16);
+        assert_eq!(u16_at(20), 1);
+        assert_eq!(u16_at(22), 1);
+        assert_eq!(u32_at(24), 22050);
+        assert_eq!(u32_at(28), 22050 * 2);
+        assert_eq!(u16_at(32), 2);
+        assert_eq!(u16_at(34), 16);
+        assert_eq!(&wav[36..40], b"data");
+        assert_eq!(u32_at(40), 4 * 2);
+        let samples: Vec<i16> = (0..4).map(|i| u16_at(44 + i * 2) as i16).collect();
+        assert_eq!(samples, [0, i16::MAX, -i16::MAX, i16::MAX / 2]);
+    }
+
+    #[test]
+    fn renders_until_the_last_note_is_released() {
+        let rate = 8000;
+        let notes = [
+            Note::new(Wave::Square(0.5), ENVELOPE, 0.0, 0.1, 440.0),
+            Note::new(Wave::Noise, ENVELOPE, 0.2, 0.05, 2000.0),
+        ];
+        let samples = render(&notes, rate);
+        // 2つ目の音が0.2 + 0.05秒で離され、0.04秒で消える
+        assert_eq!(
+            samples.len(),
+            ((0.2 + 0.05 + 0.04) * rate as f32).ceil() as usize
+        );
+        assert!(render(&[], rate).is_empty());
+    }
+
+    #[test]
+    fn envelope_starts_and_ends_at_zero() {
+        let hold = 0.1;
+        assert_eq!(ENVELOPE.level(0.0, hold), 0.0);
+        assert_eq!(ENVELOPE.level(ENVELOPE.attack, hold), 1.0);
+        assert_eq!(ENVELOPE.level(0.05, hold), ENVELOPE.sustain);
+        assert_eq!(ENVELOPE.level(hold + ENVELOPE.release, hold), 0.0);
+        assert_eq!(ENVELOPE.level(1.0, hold), 0.0);
+        // 鳴らした波形も0から始まり、最後は0に近づく
+        let samples = render(
+            &[Note::new(Wave::Square(0.5), ENVELOPE, 0.0, hold, 440.0)],
+            8000,
+        );
+        assert_eq!(samples[0], 0.0);
+        assert!(samples.last().unwrap().abs() < 0.01);
+    }
+}
@@ -0,0 +1,616 @@
+// 描画なしでゲームのルールを動かすリグレッションテスト
+use bevy::ecs::event::{Events, ManualEventReader};
+use bevy::ecs::schedule::ShouldRun;
+use bevy::ecs::system::{CommandQueue, SystemState};
+use bevy::prelude::*;
+use proptest::prelude::*;
+use proptest::test_runner::TestCaseError;
+
+use crate::arena::{spawn_wall, Arena, Wall, WallMode, SHRINK_TICKS};
+use crate::bindings::{ControlScheme, InputMap};
+use crate::gamepad::Controllers;
+use crate::hunger::HungerRule;
+use crate::level::{Level, Portal};
+use crate::modes::{GameMode, RunClock, RunOverEvent};
+use crate::raster;
+use crate::screen::{self, Screen, SingleStep};
+use crate::snapshot::{assert_board, spawn_board, Board, Snapshot};
+use crate::theme::Theme;
+use crate::{
+    food_spawner, movement_systems, snake_movement, snake_movement_input, try_turn, DeathCause,
+    Direction, FoodSpawnEvent, GameOverEvent, GrowthEvent, LastTailPosition, LastTurns, Position,
+    SnakeHead, SnakeSegments,
+};
+
+// ASCIIの盤面から作った、ウィンドウもタイマーもないApp
+// tickを呼ぶたびに、スネークが1マス進むティックの処理を1回だけ動かす
+struct Harness {
+    app: App,
+    game_over_reader: ManualEventReader<GameOverEvent>,
+    width: u32,
+    height: u32,
+}
+
+impl Harness {
+    // 盤面全体をアリーナにするので、盤面の端から出ると死ぬ
+    fn new(board: &str) -> Self {
+        let board = Snapshot::from_ascii(board).unwrap();
+        let mut app = App::new();
+        app.insert_resource(SnakeSegments::default())
+            .insert_resource(LastTailPosition::default())
+            .insert_resource::<u32>(board.score)
+            .insert_resource(GameMode::Endless)
+            .insert_resource(HungerRule(false))
+            .insert_resource(RunClock::default())
+            .insert_resource(LastTurns::default())
+            .insert_resource(Level::default())
+            .insert_resource(Arena {
+                min_x: 0,
+                min_y: 0,
+                max_x: board.width - 1,
+                max_y: board.height - 1,
+            })
+            .insert_resource(Input::<KeyCode>::default())
+            .insert_resource(InputMap::default())
+            .insert_resource(ControlScheme::Absolute)
+            .insert_resource(WallMode::Solid)
+            .insert_resource(Controllers::default())
+            .insert_resource(Input::<GamepadButton>::default())
+            .insert_resource(Axis::<GamepadAxis>::default())
+            .add_event::<GrowthEvent>()
+            .add_event::<GameOverEvent>()
+            .add_event::<RunOverEvent>()
+            .add_system(snake_movement_input.before(snake_movement))
+            .add_system_set(movement_systems());
+
+        let mut queue = CommandQueue::default();
+        let mut segments = SnakeSegments::default();
+        let mut commands = Commands::new(&mut queue, &app.world);
+        spawn_board(&mut commands, &mut segments, &board);
+        for wall in &board.walls {
+            spawn_wall(&mut commands, *wall);
+        }
+        queue.apply(&mut app.world);
+        app.insert_resource(segments);
+
+        Self {
+            app,
+            game_over_reader: ManualEventReader::default(),
+            width: board.width,
+            height: board.height,
+        }
+    }
+
+    // 本物のゲームと同じように、Foodがなくなったらティックごとに新しいFoodを置く
+    fn spawning_food(mut self) -> Self {
+        self.app
+            .add_event::<FoodSpawnEvent>()
+            .add_system(food_spawner.after(snake_movement));
+        self
+    }
+
+    // 端を通り抜けるようにする
+    fn wrapping(mut self) -> Self {
+        self.app.insert_resource(WallMode::Wrap);
+        self
+    }
+
+    // キーを押したまま1ティック進める（離した後は、次のフレームのようにjust_pressedを消す）
+    fn press(&mut self, key: KeyCode) {
+        self.app.world.resource_mut::<Input<KeyCode>>().press(key);
+        self.tick();
+        let mut input = self.app.world.resource_mut::<Input<KeyCode>>();
+        input.release(key);
+        input.clear();
+    }
+
+    fn tick(&mut self) {
+        self.app.update();
+    }
+
+    // 直前のティックでゲームオーバーになったか
+    fn died(&mut self) -> bool {
+        self.death().is_some()
+    }
+
+    // 直前のティックでゲームオーバーになった原因
+    fn death(&mut self) -> Option<DeathCause> {
+        let events = self.app.world.resource::<Events<GameOverEvent>>();
+        // 読んだイベントは消化して、次のティックでは数えない
+        let causes: Vec<DeathCause> = self
+            .game_over_reader
+            .iter(events)
+            .map(|GameOverEvent(cause)| *cause)
+            .collect();
+        causes.last().copied()
+    }
+
+    // 今の盤面（大きさはテストの盤面に合わせる）
+    fn board(&mut self) -> Snapshot {
+        let mut state: SystemState<Board> = SystemState::new(&mut self.app.world);
+        Snapshot {
+            width: self.width,
+            height: self.height,
+            ..state.get_mut(&mut self.app.world).snapshot()
+        }
+    }
+}
+
+#[test]
+fn moves_one_cell_per_tick() {
+    let mut game = Harness::new(
+        "
+        score 0
+        ......
+        .oo>..
+        ......
+        ",
+    );
+    game.tick();
+    assert!(!game.died());
+    assert_board!(
+        game.board(),
+        "
+        score 0
+        ......
+        ..oo>.
+        ......
+        "
+    );
+}
+
+#[test]
+fn dies_leaving_every_edge() {
+    let boards = [
+        // 左端（x = 0）
+        "
+        score 0
+        ...
+        <o.
+        ...
+        ",
+        // 右端
+        "
+        score 0
+        ...
+        .o>
+        ...
+        ",
+        // 上端
+        "
+        score 0
+        .^.
+        .o.
+        ...
+        ",
+        // 下端（y = 0）
+        "
+        score 0
+        ...
+        .o.
+        .v.
+        ",
+    ];
+    for board in boards {
+        let mut game = Harness::new(board);
+        game.tick();
+        assert!(game.died(), "survived leaving the board:{}", board);
+    }
+}
+
+#[test]
+fn wraps_around_the_edges() {
+    let mut game = Harness::new(
+        "
+        score 0
+        ....
+        ..o>
+        ....
+        ",
+    )
+    .wrapping();
+    game.tick();
+    assert!(!game.died());
+    assert_board!(
+        game.board(),
+        "
+        score 0
+        ....
+        >..o
+        ....
+        "
+    );
+    // 左端（x = 0）から出ても右端に戻る
+    game.press(KeyCode::Down);
+    game.press(KeyCode::Left);
+    assert!(!game.died());
+    assert_board!(
+        game.board(),
+        "
+        score 0
+        ....
+        ....
+        o..<
+        "
+    );
+}
+
+#[test]
+fn dies_on_a_wall() {
+    let mut game = Harness::new(
+        "
+        score 0
+        .....
+        .o>#.
+        .....
+        ",
+    );
+    game.tick();
+    assert_eq!(game.death(), Some(DeathCause::Wall));
+}
+
+#[test]
+fn shrinking_skips_walls_and_closes_covered_portals() {
+    let mut game = Harness::new(
+        "
+        score 0
+        ..........
+        ..........
+        ..........
+        ......1...
+        #.........
+        ....^.....
+        ...1......
+        0....0....
+        ..........
+        ..........
+        ",
+    );
+    let world = &mut game.app.world;
+    // Harnessは壁しか置かないので、盤面と同じ位置にポータルを置く
+    for (pair, a, b) in [(0, (0, 2), (5, 2)), (1, (3, 3), (6, 6))] {
+        for (entrance, exit) in [(a, b), (b, a)] {
+            world
+                .spawn()
+                .insert(Position {
+                    x: entrance.0,
+                    y: entrance.1,
+                })
+                .insert(Portal {
+                    exit: Position {
+                        x: exit.0,
+                        y: exit.1,
+                    },
+                    pair,
+                });
+        }
+    }
+    world.insert_resource(GameMode::BattleRoyale);
+    world.resource_mut::<RunClock>().ticks = SHRINK_TICKS - 1;
+    game.tick();
+    assert!(!game.died());
+
+    let world = &mut game.app.world;
+    // 外周の36マスがそれぞれ1つずつ壁になる（元からあった壁に重ねない）
+    let mut walls: Vec<(u32, u32)> = world
+        .query_filtered::<&Position, With<Wall>>()
+        .iter(world)
+        .map(|pos| (pos.x, pos.y))
+        .collect();
+    walls.sort();
+    let count = walls.len();
+    walls.dedup();
+    assert_eq!(count, walls.len());
+    assert_eq!(count, 36);
+    // 外周に掛かった0番のポータルは対ごと消え、内側の1番のポータルは残る
+    let pairs: Vec<usize> = world
+        .query::<&Portal>()
+        .iter(world)
+        .map(|portal| portal.pair)
+        .collect();
+    assert_eq!(pairs, [1, 1]);
+}
+
+#[test]
+fn dies_running_into_itself() {
+    let mut game = Harness::new(
+        "
+        score 0
+        ......
+        .ooo..
+        .o<o..
+        ...o..
+        ",
+    );
+    game.press(KeyCode::Up);
+    assert_eq!(game.death(), Some(DeathCause::Itself));
+}
+
+#[test]
+fn eats_food_and_grows_where_the_tail_was() {
+    let mut game = Harness::new(
+        "
+        score 4
+        ......
+        .oo>*.
+        ......
+        ",
+    );
+    game.tick();
+    assert!(!game.died());
+    assert!(game.app.world.resource::<LastTailPosition>().0 == Some(Position { x: 1, y: 1 }));
+    assert_board!(
+        game.board(),
+        "
+        score 5
+        ......
+        .ooo>.
+        ......
+        "
+    );
+    game.tick();
+    assert_board!(
+        game.board(),
+        "
+        score 5
+        ......
+        ..ooo>
+        ......
+        "
+    );
+}
+
+#[test]
+fn cannot_reverse_into_its_body() {
+    let mut game = Harness::new(
+        "
+        score 0
+        .......
+        .oo>...
+        .......
+        ",
+    );
+    game.press(KeyCode::Left);
+    assert!(!game.died());
+    assert_board!(
+        game.board(),
+        "
+        score 0
+        .......
+        ..oo>..
+        .......
+        "
+    );
+    game.press(KeyCode::Up);
+    assert_board!(
+        game.board(),
+        "
+        score 0
+        ....^..
+        ...oo..
+        .......
+        "
+    );
+}
+
+#[test]
+fn relative_controls_turn_from_the_heading() {
+    let mut game = Harness::new(
+        "
+        score 0
+        .......
+        .......
+        .oo>...
+        .......
+        .......
+        ",
+    );
+    game.app.insert_resource(ControlScheme::Relative);
+    // 右を向いているときに右を押すと下に曲がる
+    game.press(KeyCode::Right);
+    // 上下は何もしない
+    game.press(KeyCode::Up);
+    assert!(!game.died());
+    assert_board!(
+        game.board(),
+        "
+        score 0
+        .......
+        .......
+        ...o...
+        ...o...
+        ...v...
+        "
+    );
+    // 下を向いているときに左を押すと、スネークから見て左の東に曲がる
+    game.press(KeyCode::Left);
+    assert_board!(
+        game.board(),
+        "
+        score 0
+        .......
+        .......
+        .......
+        ...o...
+        ...o>..
+        "
+    );
+}
+
+#[test]
+fn turns_at_most_once_per_tick() {
+    // 右に進んでいるときに、1ティックの間に上、左と曲がると真後ろを向いてしまう
+    let mut head = SnakeHead {
+        direction: Direction::Right,
+    };
+    let mut clock = RunClock::default();
+    let mut last_turns = LastTurns::default();
+    try_turn(&mut head, Direction::Up, 0, &clock, &mut last_turns);
+    try_turn(&mut head, Direction::Left, 0, &clock, &mut last_turns);
+    assert!(head.direction == Direction::Up);
+    // 他のプレイヤーは別に数える
+    let mut other = SnakeHead {
+        direction: Direction::Right,
+    };
+    try_turn(&mut other, Direction::Down, 1, &clock, &mut last_turns);
+    assert!(other.direction == Direction::Down);
+    // 次のティックでは曲がれる
+    clock.ticks += 1;
+    try_turn(&mut head, Direction::Left, 0, &clock, &mut last_turns);
+    assert!(head.direction == Direction::Left);
+}
+
+// ランダムな入力を流し込んで、どんなプレイでも崩れてはいけない性質を確かめる
+// 失敗したときは、proptestが入力を縮めて、崩れる最小の入力と盤面を表示する
+const FUZZ_RUNS: u32 = 200;
+const FUZZ_TICKS: usize = 300;
+
+fn check_invariants(board: &Snapshot, last_score: u32) -> Result<(), String> {
+    let snake = &board.snake;
+    for pair in snake.windows(2) {
+        let (a, b) = (pair[0], pair[1]);
+        if a.x.abs_diff(b.x) + a.y.abs_diff(b.y) != 1 {
+            return Err(format!(
+                "segments ({}, {}) and ({}, {}) are not adjacent",
+                a.x, a.y, b.x, b.y
+            ));
+        }
+    }
+    for (i, pos) in snake.iter().enumerate() {
+        if snake[i + 1..].contains(pos) {
+            return Err(format!("two segments share ({}, {})", pos.x, pos.y));
+        }
+    }
+    // スコア0の1マスから始めるエンドレスなので、長さは1 + 食べたFoodの数 = 1 + スコア
+    if snake.len() as u32 != 1 + board.score {
+        return Err(format!("length {} with score {}", snake.len(), board.score));
+    }
+    if let Some(food) = board.food.iter().find(|food| snake.contains(food)) {
+        return Err(format!(
+            "food at ({}, {}) is inside the snake",
+            food.x, food.y
+        ));
+    }
+    if board.score < last_score {
+        return Err(format!(
+            "score went down from {} to {}",
+            last_score, board.score
+        ));
+    }
+    Ok(())
+}
+
+// 1ティックの入力。Noneなら何も押さずにティックを進める
+fn inputs() -> impl Strategy<Value = Vec<Option<Direction>>> {
+    let direction = prop_oneof![
+        Just(Direction::Up),
+        Just(Direction::Down),
+        Just(Direction::Left),
+        Just(Direction::Right),
+    ];
+    prop::collection::vec(prop::option::of(direction), 0..FUZZ_TICKS)
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(FUZZ_RUNS))]
+
+    #[test]
+    fn random_play_keeps_the_board_consistent(inputs in inputs()) {
+        // 小さい盤面にして、Foodを食べたり壁や自分にぶつかったりしやすくする
+        let mut game = Harness::new(
+            "
+            score 0
+            ......
+            ......
+            ..^...
+            ......
+            ......
+            ",
+        )
+        .spawning_food();
+        let mut last_score = 0;
+        for input in inputs {
+            match input {
+                Some(Direction::Up) => game.press(KeyCode::Up),
+                Some(Direction::Down) => game.press(KeyCode::Down),
+                Some(Direction::Left) => game.press(KeyCode::Left),
+                Some(Direction::Right) => game.press(KeyCode::Right),
+                None => game.tick(),
+            }
+            // 死んだティックでは、ヘッドが自分や壁に重なっていてよい
+            if game.died() {
+                break;
+            }
+            let board = game.board();
+            check_invariants(&board, last_score).map_err(|err| {
+                TestCaseError::fail(format!("{}\n{}", err, board.to_ascii()))
+            })?;
+            last_score = board.score;
+        }
+    }
+}
+
+#[test]
+fn single_step_runs_one_tick_while_paused() {
+    let mut world = World::new();
+    world.insert_resource(Screen::Paused);
+    world.insert_resource(SingleStep::default());
+    let mut begin_step = IntoSystem::into_system(screen::begin_step);
+    begin_step.initialize(&mut world);
+    // 移動とハザードのように、別々の実行条件を持つ2つのSystemSet
+    let mut movement = IntoSystem::into_system(screen::while_playing_or_stepping);
+    movement.initialize(&mut world);
+    let mut hazards = IntoSystem::into_system(screen::while_playing_or_stepping);
+    hazards.initialize(&mut world);
+    // 一時停止中はタイマーが進んでも動かない
+    begin_step.run((), &mut world);
+    assert_eq!(movement.run(ShouldRun::Yes, &mut world), ShouldRun::No);
+    // 1ティックだけ進める要求があれば、次のフレームでタイマーに関係なくどちらも1回ずつ動く
+    world.resource_mut::<SingleStep>().requested = true;
+    begin_step.run((), &mut world);
+    assert_eq!(movement.run(ShouldRun::No, &mut world), ShouldRun::Yes);
+    assert_eq!(hazards.run(ShouldRun::No, &mut world), ShouldRun::Yes);
+    // その次のフレームでは動かない
+    begin_step.run((), &mut world);
+    assert_eq!(movement.run(ShouldRun::Yes, &mut world), ShouldRun::No);
+    assert_eq!(hazards.run(ShouldRun::Yes, &mut world), ShouldRun::No);
+    // 再開したらタイマーに従う
+    *world.resource_mut::<Screen>() = Screen::Playing;
+    assert_eq!(
+        movement.run(ShouldRun::YesAndCheckAgain, &mut world),
+        ShouldRun::YesAndCheckAgain
+    );
+}
+
+#[test]
+fn renders_a_known_board_to_pixels() {
+    let board = Snapshot::from_ascii(
+        "
+        score 0
+        .....
+        .o>#.
+        .....
+        ",
+    )
+    .unwrap();
+    // 1マス4ピクセルなら、スコアの帯は7ピクセルで、スネークのタイルは3ピクセル四方
+    // 画像は30x30のアリーナではなく、5x3の盤面の大きさになる
+    let image = raster::render(&board, &Theme::default(), 4);
+    assert_eq!(image.dimensions(), (5 * 4, 7 + 3 * 4));
+    let pixel = |x: u32, y: u32| image.get_pixel(x, y).0;
+    let background = [10, 10, 10, 255];
+    let wall = [89, 89, 102, 255];
+    // 真ん中の行はy = 7 + 4から始まる。ヘッドは3列目、胴体は2列目、壁は4列目
+    assert_eq!(pixel(8, 11), [255, 0, 0, 255]);
+    assert_eq!(pixel(10, 13), [255, 0, 0, 255]);
+    assert_eq!(pixel(11, 14), background);
+    assert_eq!(pixel(5, 12), [255, 179, 153, 255]);
+    assert_eq!(pixel(12, 11), wall);
+    assert_eq!(pixel(15, 14), wall);
+    // 一番下の行と右下の角は床
+    assert_eq!(pixel(1, 16), background);
+    assert_eq!(pixel(19, 18), background);
+    // スコアの0は左上から1ドット空けて描く
+    assert_eq!(image.get_pixel(1, 1).0, [255, 255, 255, 255]);
+    assert_eq!(image.get_pixel(0, 0).0, background);
+}
@@ -0,0 +1,228 @@
+use std::fs;
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::arena::{GridTile, Wall};
+use crate::hazard::Hazard;
+use crate::level::Portal;
+use crate::settings::Settings;
+use crate::{Food, ScoreText, SnakeHead, SnakeSegment};
+
+// テーマファイルを置くディレクトリ
+const THEMES_DIR: &str = "assets/themes";
+// テーマファイルが見つからなければこの名前のテーマから始める
+const DEFAULT_THEME: &str = "dark";
+
+// ゲームの見た目（色、テクスチャ、フォント）
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Theme {
+    pub name: String,
+    pub background: Color,
+    pub snake_head: Color,
+    pub snake_body: Color,
+    pub food: Color,
+    pub wall: Color,
+    pub hazard: Color,
+    // ポータルの対ごとの色
+    pub portals: Vec<Color>,
+    // グリッドの偶数マスと奇数マスの色
+    pub grid_even: Color,
+    pub grid_odd: Color,
+    pub text: Color,
+    pub font: String,
+    // スネークとFoodを描画するスプライトシート（なければ単色の四角形で描画する）
+    pub sprite_sheet: Option<String>,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self {
+            name: DEFAULT_THEME.to_string(),
+            background: Color::rgb(0.04, 0.04, 0.04),
+            snake_head: Color::rgb(1.0, 0.0, 0.0),
+            snake_body: Color::rgb(1.0, 0.7, 0.6),
+            food: Color::rgb(1.0, 0.0, 1.0),
+            wall: Color::rgb(0.35, 0.35, 0.4),
+            hazard: Color::rgb(1.0, 0.35, 0.0),
+            portals: vec![
+                Color::rgb(0.0, 0.8, 1.0),
+                Color::rgb(1.0, 0.6, 0.0),
+                Color::rgb(0.5, 1.0, 0.3),
+            ],
+            grid_even: Color::rgb(0.07, 0.07, 0.07),
+            grid_odd: Color::rgb(0.1, 0.1, 0.1),
+            text: Color::WHITE,
+            font: "dejavu-sans-mono/DejaVuSansMono.ttf".to_string(),
+            sprite_sheet: Some("sprites/snake.png".to_string()),
+        }
+    }
+}
+
+impl Theme {
+    pub fn portal(&self, pair: usize) -> Color {
+        self.portals
+            .get(pair % self.portals.len().max(1))
+            .copied()
+            .unwrap_or(self.wall)
+    }
+}
+
+// 選べるテーマの一覧と、今使っているテーマ
+pub struct Themes {
+    themes: Vec<Theme>,
+    current: usize,
+}
+
+impl Themes {
+    // assets/themes のテーマファイルを名前順に読み込む
+    pub fn load() -> Self {
+        let mut themes: Vec<Theme> = fs::read_dir(THEMES_DIR)
+            .map(|entries| {
+                entries
+                    .filter_map(|entry| entry.ok())
+                    .filter(|entry| entry.path().extension() == Some("ron".as_ref()))
+                    .filter_map(|entry| {
+                        let path = entry.path();
+                        fs::read_to_string(&path)
+                            .map_err(|err| err.to_string())
+                            .and_then(|text| ron::from_str(&text).map_err(|err| err.to_string()))
+                            .map_err(|err| warn!("Failed to load theme {:?}: {}", path, err))
+                            .ok()
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+        if themes.is_empty() {
+            themes.push(Theme::default());
+        }
+        themes.sort_by(|a, b| a.name.cmp(&b.name));
+        let current = themes
+            .iter()
+            .position(|theme| theme.name == DEFAULT_THEME)
+            .unwrap_or(0);
+        Self { themes, current }
+    }
+
+    pub fn current(&self) -> &Theme {
+        &self.themes[self.current]
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.themes.iter().map(|theme| theme.name.as_str())
+    }
+
+    // 名前でテーマを選ぶ。見つからなければfalseを返す
+    pub fn select(&mut self, name: &str) -> bool {
+        match self.themes.iter().position(|theme| theme.name == name) {
+            Some(index) => {
+                self.current = index;
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn cycle(&mut self) {
+        self.current = (self.current + 1) % self.themes.len();
+    }
+
+    pub fn cycle_back(&mut self) {
+        self.current = (self.current + self.themes.len() - 1) % self.themes.len();
+    }
+}
+
+// テーマを読み込み、保存したテーマかコマンドライン引数の --theme でテーマを選ぶ
+// --flat があればスプライトシートを使わずに単色の四角形で描画する
+pub fn themes_from_args(settings: &Settings) -> Themes {
+    let mut themes = Themes::load();
+    if let Some(name) = &settings.theme {
+        themes.select(name);
+    }
+    let args: Vec<String> = std::env::args().collect();
+    if let Some(name) = args
+        .iter()
+        .position(|arg| arg == "--theme")
+        .and_then(|i| args.get(i + 1))
+    {
+        if !themes.select(name) {
+            warn!(
+                "unknown theme: {} (available: {})",
+                name,
+                themes.names().collect::<Vec<_>>().join(", ")
+            );
+        }
+    }
+    if args.iter().any(|arg| arg == "--flat") {
+        for theme in &mut themes.themes {
+            theme.sprite_sheet = None;
+        }
+    }
+    themes
+}
+
+pub fn cycle_theme(keyboard_input: Res<Input<KeyCode>>, mut themes: ResMut<Themes>) {
+    // Tキーでテーマを切り替える
+    if keyboard_input.just_pressed(KeyCode::T) {
+        themes.cycle();
+        info!("Theme: {}", themes.current().name);
+    }
+}
+
+pub fn apply_theme(
+    themes: Res<Themes>,
+    asset_server: Res<AssetServer>,
+    mut clear_color: ResMut<ClearColor>,
+    mut sprites: Query<(
+        &mut Sprite,
+        ChangeTrackers<Sprite>,
+        Option<&SnakeHead>,
+        Option<&SnakeSegment>,
+        Option<&Food>,
+        Option<&Wall>,
+        Option<&Hazard>,
+        Option<&Portal>,
+        Option<&GridTile>,
+    )>,
+    mut texts: Query<&mut Text, With<ScoreText>>,
+) {
+    let theme = themes.current();
+    // テーマが変わったら全てのスプライトを、そうでなければ新しく生成されたスプライトだけを塗る
+    for (mut sprite, tracker, head, segment, food, wall, hazard, portal, grid) in sprites.iter_mut() {
+        if !themes.is_changed() && !tracker.is_added() {
+            continue;
+        }
+        let color = if head.is_some() {
+            theme.snake_head
+        } else if segment.is_some() {
+            theme.snake_body
+        } else if food.is_some() {
+            theme.food
+        } else if wall.is_some() {
+            theme.wall
+        } else if hazard.is_some() {
+            theme.hazard
+        } else if let Some(portal) = portal {
+            theme.portal(portal.pair)
+        } else if let Some(grid) = grid {
+            if grid.odd {
+                theme.grid_odd
+            } else {
+                theme.grid_even
+            }
+        } else {
+            continue;
+        };
+        sprite.color = color;
+    }
+    if !themes.is_changed() {
+        return;
+    }
+    clear_color.0 = theme.background;
+    for mut text in texts.iter_mut() {
+        for section in text.sections.iter_mut() {
+            section.style.color = theme.text;
+            section.style.font = asset_server.load(theme.font.as_str());
+        }
+    }
+}
@@ -0,0 +1,74 @@
+use bevy::ecs::schedule::ShouldRun;
+use bevy::prelude::*;
+
+use crate::{Position, SnakeSegment};
+
+// 1ティックの長さ（ミリ秒）の範囲と、設定の画面で1回に変える量
+pub const MIN_TICK_MILLIS: u32 = 50;
+pub const MAX_TICK_MILLIS: u32 = 300;
+pub const TICK_MILLIS_STEP: u32 = 25;
+
+// スネークが1マス動く間隔と、前のティックからの経過時間
+// FixedTimestepと同じように動くが、間隔をゲーム中に変えられる
+pub struct TickTimer {
+    pub millis: u32,
+    accumulated: f64,
+    looping: bool,
+}
+
+impl TickTimer {
+    pub fn new(millis: u32) -> Self {
+        Self {
+            millis: millis.clamp(MIN_TICK_MILLIS, MAX_TICK_MILLIS),
+            accumulated: 0.0,
+            looping: false,
+        }
+    }
+
+    fn step(&self) -> f64 {
+        self.millis as f64 / 1000.0
+    }
+
+    // 前のティックから次のティックまでの進み具合（0〜1）
+    pub fn progress(&self) -> f32 {
+        (self.accumulated / self.step()).min(1.0) as f32
+    }
+}
+
+// 1ティック分の時間が経つたびに実行する実行条件。遅れた分はまとめて実行する
+pub fn movement_tick(time: Res<Time>, mut timer: ResMut<TickTimer>) -> ShouldRun {
+    if !timer.looping {
+        timer.accumulated += time.delta_seconds_f64();
+    }
+    let step = timer.step();
+    if timer.accumulated >= step {
+        timer.accumulated -= step;
+        timer.looping = true;
+        ShouldRun::YesAndCheckAgain
+    } else {
+        timer.looping = false;
+        ShouldRun::No
+    }
+}
+
+// ティックの間のスネークの動きを滑らかに描画するかどうか
+pub struct Interpolation(pub bool);
+
+// 前のティックでのセグメントの位置（滑らかに描画するときの動き始めの位置）
+#[derive(Component)]
+pub struct PreviousPosition(pub Position);
+
+// スネークが動く前に、今の位置を前のティックの位置として覚える
+pub fn remember_positions(
+    mut commands: Commands,
+    mut segments: Query<(Entity, &Position, Option<&mut PreviousPosition>), With<SnakeSegment>>,
+) {
+    for (entity, position, previous) in segments.iter_mut() {
+        match previous {
+            Some(mut previous) => previous.0 = *position,
+            None => {
+                commands.entity(entity).insert(PreviousPosition(*position));
+            }
+        }
+    }
+}
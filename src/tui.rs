@@ -0,0 +1,278 @@
+use std::fmt::Write as _;
+use std::io::{Read, Write};
+use std::process::Command;
+use std::sync::mpsc::{channel, Receiver};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use bevy::app::{AppExit, ScheduleRunnerSettings};
+use bevy::prelude::*;
+
+use crate::arena::Wall;
+use crate::hazard::Hazard;
+use crate::hunger::Hunger;
+use crate::level::Portal;
+use crate::lives::Lives;
+use crate::modes::{self, GameMode, HighScores, RunClock};
+use crate::screen::Screen;
+use crate::theme::{self, Themes};
+use crate::{Food, Position, SnakeHead, SnakeSegment, SnakeSegments, ARENA_HEIGHT, ARENA_WIDTH};
+
+// 画面を描き直す間隔（秒）
+const FRAME_TIME: f64 = 1.0 / 30.0;
+
+// 英字のキー（aから順に）
+const LETTERS: [KeyCode; 26] = [
+    KeyCode::A,
+    KeyCode::B,
+    KeyCode::C,
+    KeyCode::D,
+    KeyCode::E,
+    KeyCode::F,
+    KeyCode::G,
+    KeyCode::H,
+    KeyCode::I,
+    KeyCode::J,
+    KeyCode::K,
+    KeyCode::L,
+    KeyCode::M,
+    KeyCode::N,
+    KeyCode::O,
+    KeyCode::P,
+    KeyCode::Q,
+    KeyCode::R,
+    KeyCode::S,
+    KeyCode::T,
+    KeyCode::U,
+    KeyCode::V,
+    KeyCode::W,
+    KeyCode::X,
+    KeyCode::Y,
+    KeyCode::Z,
+];
+
+// 標準入力を読むスレッドから届いたバイト列
+struct KeyReceiver(Mutex<Receiver<Vec<u8>>>);
+
+// sttyで端末の設定を変える。設定を読むときは出力を返す
+fn stty(args: &[&str]) -> Option<String> {
+    Command::new("stty")
+        .args(args)
+        .stdin(std::process::Stdio::inherit())
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .map(|output| String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+// 端末を1キーずつ読める状態にし、捨てるときに元に戻す
+// Systemがパニックしても巻き戻しの途中で戻すので、エコーとシグナルが切れたままにならない
+struct RawTerminal {
+    saved: Option<String>,
+}
+
+impl RawTerminal {
+    fn enter() -> Self {
+        // 1キーずつ読めるように、行バッファとエコーとCtrl-Cのシグナルを切る
+        let saved = stty(&["-g"]);
+        stty(&["-icanon", "-echo", "-isig"]);
+        // カーソルを隠して画面を消す
+        print!("\x1b[?25l\x1b[2J");
+        Self { saved }
+    }
+}
+
+impl Drop for RawTerminal {
+    fn drop(&mut self) {
+        // 端末の設定とカーソルを元に戻す
+        print!("\x1b[0m\x1b[?25h\r\n");
+        std::io::stdout().flush().ok();
+        match &self.saved {
+            Some(saved) => stty(&[saved.as_str()]),
+            None => stty(&["sane"]),
+        };
+    }
+}
+
+// ウィンドウを開かずに、ウィンドウ版と同じルールのゲームをターミナルで遊ぶ
+pub fn run() {
+    let _terminal = RawTerminal::enter();
+    let (sender, receiver) = channel();
+    std::thread::spawn(move || {
+        let mut stdin = std::io::stdin();
+        let mut buf = [0; 16];
+        while let Ok(n) = stdin.read(&mut buf) {
+            if n == 0 || sender.send(buf[..n].to_vec()).is_err() {
+                break;
+            }
+        }
+    });
+
+    let frame_time = Duration::from_secs_f64(FRAME_TIME);
+    let mut app = App::new();
+    crate::add_game_rules(&mut app);
+    let themes = theme::themes_from_args(app.world.resource());
+    crate::audio::add_audio(&mut app)
+        .insert_resource(ScheduleRunnerSettings::run_loop(frame_time))
+        .add_plugins(MinimalPlugins)
+        // 矢印キーはウィンドウ版と同じInput<KeyCode>に押されたことにして、同じSystemで処理する
+        .insert_resource(Input::<KeyCode>::default())
+        // ゲームパッドは使わないが、入力を読むSystemはウィンドウ版と共通なので空の入力を置く
+        .insert_resource(Input::<GamepadButton>::default())
+        .insert_resource(Axis::<GamepadAxis>::default())
+        .insert_resource(KeyReceiver(Mutex::new(receiver)))
+        .insert_resource(themes)
+        .add_system_to_stage(CoreStage::PreUpdate, read_keys)
+        .add_system_to_stage(CoreStage::Last, draw);
+    app.run();
+}
+
+fn read_keys(
+    receiver: Res<KeyReceiver>,
+    mut keyboard_input: ResMut<Input<KeyCode>>,
+    mut exit: EventWriter<AppExit>,
+) {
+    // ターミナルは離したことを伝えないので、押されたキーは1フレームだけ押されたことにする
+    for key in [KeyCode::Up, KeyCode::Down, KeyCode::Left, KeyCode::Right]
+        .into_iter()
+        .chain(LETTERS)
+    {
+        keyboard_input.reset(key);
+    }
+    let bytes: Vec<u8> = receiver.0.lock().unwrap().try_iter().flatten().collect();
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i..] {
+            // 矢印キーは ESC [ A〜D で届く
+            [0x1b, b'[', arrow, ..] => {
+                let key = match arrow {
+                    b'A' => Some(KeyCode::Up),
+                    b'B' => Some(KeyCode::Down),
+                    b'C' => Some(KeyCode::Right),
+                    b'D' => Some(KeyCode::Left),
+                    _ => None,
+                };
+                if let Some(key) = key {
+                    keyboard_input.press(key);
+                }
+                i += 3;
+            }
+            // qかCtrl-Cで終了する
+            [b'q', ..] | [0x03, ..] => {
+                exit.send(AppExit);
+                i += 1;
+            }
+            // 他の英字は、WASDなどに割り当てたキーとして押されたことにする
+            [letter @ b'a'..=b'z', ..] => {
+                keyboard_input.press(LETTERS[(letter - b'a') as usize]);
+                i += 1;
+            }
+            _ => i += 1,
+        }
+    }
+}
+
+// ANSIエスケープシーケンスの24bitカラー
+fn ansi_color(color: Color, layer: u8) -> String {
+    let [r, g, b, _] = color.as_rgba_f32();
+    let channel = |c: f32| (c.clamp(0.0, 1.0) * 255.0).round() as u8;
+    format!(
+        "\x1b[{};2;{};{};{}m",
+        layer,
+        channel(r),
+        channel(g),
+        channel(b)
+    )
+}
+
+fn draw(
+    themes: Res<Themes>,
+    game_screen: Res<Screen>,
+    mode: Res<GameMode>,
+    score: Res<u32>,
+    lives: Res<Lives>,
+    segments: Res<SnakeSegments>,
+    clock: Res<RunClock>,
+    high_scores: Res<HighScores>,
+    hungers: Query<&Hunger>,
+    tiles: Query<(
+        &Position,
+        Option<&Visibility>,
+        Option<&SnakeHead>,
+        Option<&SnakeSegment>,
+        Option<&Food>,
+        Option<&Wall>,
+        Option<&Hazard>,
+        Option<&Portal>,
+    )>,
+) {
+    // 1マスを横2文字のブロック文字で描く。重なったマスは優先度の高いものを描く
+    let theme = themes.current();
+    let mut cells: Vec<Option<(u8, &str, Color)>> =
+        vec![None; (ARENA_WIDTH * ARENA_HEIGHT) as usize];
+    for (pos, visibility, head, segment, food, wall, hazard, portal) in tiles.iter() {
+        if matches!(visibility, Some(visibility) if !visibility.is_visible) {
+            continue;
+        }
+        let tile = if head.is_some() {
+            (5, "██", theme.snake_head)
+        } else if segment.is_some() {
+            (4, "▓▓", theme.snake_body)
+        } else if hazard.is_some() {
+            (3, "▒▒", theme.hazard)
+        } else if food.is_some() {
+            (2, "▐▌", theme.food)
+        } else if let Some(portal) = portal {
+            (1, "░░", theme.portal(portal.pair))
+        } else if wall.is_some() {
+            (0, "██", theme.wall)
+        } else {
+            continue;
+        };
+        if pos.x >= ARENA_WIDTH || pos.y >= ARENA_HEIGHT {
+            continue;
+        }
+        let cell = &mut cells[(pos.y * ARENA_WIDTH + pos.x) as usize];
+        match cell {
+            Some((priority, _, _)) if *priority >= tile.0 => {}
+            _ => *cell = Some(tile),
+        }
+    }
+
+    // 画面の左上から描き直す（一番上の行がyの最大）
+    let mut screen = String::from("\x1b[H");
+    let background = ansi_color(theme.background, 48);
+    for y in (0..ARENA_HEIGHT).rev() {
+        screen.push_str(&background);
+        for x in 0..ARENA_WIDTH {
+            match cells[(y * ARENA_WIDTH + x) as usize] {
+                Some((_, glyph, color)) => {
+                    screen.push_str(&ansi_color(color, 38));
+                    screen.push_str(glyph);
+                }
+                None => screen.push_str("  "),
+            }
+        }
+        screen.push_str("\x1b[0m\x1b[K\r\n");
+    }
+    let hud = modes::hud_text(
+        *mode,
+        *score,
+        lives.remaining,
+        segments.len(),
+        hungers.iter().next(),
+        &clock,
+        &high_scores,
+    );
+    for line in hud.lines() {
+        let _ = write!(screen, "{}\x1b[K\r\n", line);
+    }
+    if *game_screen == Screen::Paused {
+        screen.push_str("Paused, p to resume, q to quit\x1b[K\r\n\x1b[J");
+    } else {
+        screen.push_str("Arrow keys to move, p to pause, q to quit\x1b[K\r\n\x1b[J");
+    }
+    let mut stdout = std::io::stdout();
+    stdout.write_all(screen.as_bytes()).ok();
+    stdout.flush().ok();
+}
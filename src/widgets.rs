@@ -0,0 +1,351 @@
+use bevy::ecs::system::SystemParam;
+use bevy::input::mouse::{MouseScrollUnit, MouseWheel};
+use bevy::prelude::*;
+
+use crate::screen::Screen;
+use crate::theme::Theme;
+
+// examples/uiのボタンとスクロールするリストを、メニューで使い回せるようにしたもの
+// ボタンはマウスだけでなく、キーボードとゲームパッドでも選んで押せる
+
+const NORMAL_BUTTON: Color = Color::rgb(0.15, 0.15, 0.15);
+const HOVERED_BUTTON: Color = Color::rgb(0.25, 0.25, 0.25);
+const PRESSED_BUTTON: Color = Color::rgb(0.35, 0.75, 0.35);
+// ボタンの大きさ（ピクセル）
+const BUTTON_WIDTH: f32 = 420.0;
+const BUTTON_HEIGHT: f32 = 52.0;
+// ボタンやラベルの周りの余白（ピクセル）
+const ITEM_MARGIN: f32 = 6.0;
+// マウスのホイール1行分のスクロール量（ピクセル）
+const SCROLL_LINE: f32 = 20.0;
+// 画面の背景の不透明度（後ろの盤面がうっすら見える）
+const BACKGROUND_ALPHA: f32 = 0.9;
+
+// 何番目のボタンか（上から0, 1, 2, ...）
+#[derive(Component)]
+pub struct WidgetButton(pub usize);
+
+// 中身がはみ出たらホイールと選んでいるボタンに合わせてスクロールするリスト
+#[derive(Component, Default)]
+pub struct ScrollingList {
+    position: f32,
+}
+
+// キーボードとゲームパッドで選んでいるボタン。countが0ならボタンを操作しない（表示していないか、キーを待っている）
+#[derive(Default)]
+pub struct Focus {
+    pub index: usize,
+    pub count: usize,
+}
+
+// ボタンが押された（Enter、ゲームパッドの下のボタン、クリック）
+pub struct Activated(pub usize);
+// 前の画面に戻る操作をした（Esc、ゲームパッドの右のボタン）
+pub struct Back;
+// 選んでいるボタンの値を左右で変える操作をした（左は-1、右は+1）
+pub struct Stepped(pub usize, pub i32);
+
+// 文字の見た目
+#[derive(Clone)]
+pub struct WidgetStyle {
+    pub font: Handle<Font>,
+    pub text: Color,
+    pub background: Color,
+}
+
+impl WidgetStyle {
+    // テーマのフォントと文字色を使い、背景はテーマの背景色を少し透かしたものにする
+    pub fn from_theme(theme: &Theme, asset_server: &AssetServer) -> Self {
+        let mut background = theme.background.as_rgba();
+        background.set_a(BACKGROUND_ALPHA);
+        Self {
+            font: asset_server.load(theme.font.as_str()),
+            text: theme.text,
+            background,
+        }
+    }
+
+    fn text_style(&self, font_size: f32) -> TextStyle {
+        TextStyle {
+            font: self.font.clone(),
+            font_size,
+            color: self.text,
+        }
+    }
+}
+
+// 開いている画面への入力（ボタンの番号と、左右で変えた行と量と、戻る操作をしたか）
+#[derive(Default)]
+pub struct ScreenInput {
+    pub activated: Option<usize>,
+    pub steps: Vec<(usize, i32)>,
+    pub back: bool,
+}
+
+// ボタンと戻る操作のイベントを、開いている画面のSystemにだけ渡す
+#[derive(SystemParam)]
+pub struct ScreenEvents<'w, 's> {
+    activated: EventReader<'w, 's, Activated>,
+    stepped: EventReader<'w, 's, Stepped>,
+    back: EventReader<'w, 's, Back>,
+}
+
+impl<'w, 's> ScreenEvents<'w, 's> {
+    // screenがownerの画面ならその入力を返す
+    // 他の画面のイベントは読み捨てる（この画面に移ったフレームのイベントも、前の画面のもの）
+    pub fn read(&mut self, screen: &ResMut<Screen>, owner: Screen) -> Option<ScreenInput> {
+        let input = ScreenInput {
+            activated: self.activated.iter().last().map(|Activated(index)| *index),
+            steps: self
+                .stepped
+                .iter()
+                .map(|Stepped(index, step)| (*index, *step))
+                .collect(),
+            back: self.back.iter().count() > 0,
+        };
+        if **screen != owner || screen.is_changed() {
+            return None;
+        }
+        Some(input)
+    }
+}
+
+// 画面全体を覆い、子を上から順に中央に並べるノード
+pub fn screen_root(style: &WidgetStyle) -> NodeBundle {
+    NodeBundle {
+        style: Style {
+            size: Size::new(Val::Percent(100.0), Val::Percent(100.0)),
+            position_type: PositionType::Absolute,
+            // Bevyの UIはyが上向きなので、上から並べるには逆順にする
+            flex_direction: FlexDirection::ColumnReverse,
+            justify_content: JustifyContent::Center,
+            align_items: AlignItems::Center,
+            ..default()
+        },
+        color: style.background.into(),
+        ..default()
+    }
+}
+
+pub fn title(parent: &mut ChildBuilder, text: &str, style: &WidgetStyle) {
+    parent.spawn_bundle(TextBundle {
+        style: Style {
+            margin: Rect::all(Val::Px(20.0)),
+            ..default()
+        },
+        text: Text::with_section(text, style.text_style(56.0), default()),
+        ..default()
+    });
+}
+
+pub fn label(parent: &mut ChildBuilder, text: &str, style: &WidgetStyle) {
+    parent.spawn_bundle(TextBundle {
+        style: Style {
+            flex_shrink: 0.0,
+            margin: Rect::all(Val::Px(ITEM_MARGIN)),
+            ..default()
+        },
+        text: Text::with_section(text, style.text_style(28.0), default()),
+        ..default()
+    });
+}
+
+pub fn button(parent: &mut ChildBuilder, index: usize, text: &str, style: &WidgetStyle) {
+    parent
+        .spawn_bundle(ButtonBundle {
+            style: Style {
+                size: Size::new(Val::Px(BUTTON_WIDTH), Val::Px(BUTTON_HEIGHT)),
+                flex_shrink: 0.0,
+                margin: Rect::all(Val::Px(ITEM_MARGIN)),
+                // 子のテキストを中央に置く
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                ..default()
+            },
+            color: NORMAL_BUTTON.into(),
+            ..default()
+        })
+        .insert(WidgetButton(index))
+        .with_children(|parent| {
+            parent.spawn_bundle(TextBundle {
+                text: Text::with_section(text, style.text_style(32.0), default()),
+                ..default()
+            });
+        });
+}
+
+// 高さheightの枠の中に、はみ出たらスクロールするリストを作る
+pub fn scrolling_list(
+    parent: &mut ChildBuilder,
+    height: f32,
+    items: impl FnOnce(&mut ChildBuilder),
+) {
+    parent
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                size: Size::new(Val::Px(BUTTON_WIDTH + 40.0), Val::Px(height)),
+                flex_direction: FlexDirection::ColumnReverse,
+                align_self: AlignSelf::Center,
+                overflow: Overflow::Hidden,
+                ..default()
+            },
+            color: Color::NONE.into(),
+            ..default()
+        })
+        .with_children(|parent| {
+            parent
+                .spawn_bundle(NodeBundle {
+                    style: Style {
+                        flex_direction: FlexDirection::ColumnReverse,
+                        flex_grow: 1.0,
+                        align_items: AlignItems::Center,
+                        ..default()
+                    },
+                    color: Color::NONE.into(),
+                    ..default()
+                })
+                .insert(ScrollingList::default())
+                .with_children(items);
+        });
+}
+
+// 上下で選び、Enterで押し、左右で値を変え、Escで戻る。ゲームパッドでは十字キーと下のボタンと右のボタン
+// マウスを乗せたボタンを選び、クリックしたボタンを押す
+pub fn navigate(
+    keys: Res<Input<KeyCode>>,
+    buttons: Res<Input<GamepadButton>>,
+    mut focus: ResMut<Focus>,
+    mut activated: EventWriter<Activated>,
+    mut stepped: EventWriter<Stepped>,
+    mut back: EventWriter<Back>,
+    interactions: Query<(&WidgetButton, &Interaction), Changed<Interaction>>,
+) {
+    if focus.count == 0 {
+        return;
+    }
+    let pad = |kind| {
+        buttons
+            .get_just_pressed()
+            .any(|GamepadButton(_, pressed)| *pressed == kind)
+    };
+    if keys.just_pressed(KeyCode::Up) || pad(GamepadButtonType::DPadUp) {
+        focus.index = (focus.index + focus.count - 1) % focus.count;
+    } else if keys.just_pressed(KeyCode::Down) || pad(GamepadButtonType::DPadDown) {
+        focus.index = (focus.index + 1) % focus.count;
+    } else if keys.just_pressed(KeyCode::Left) || pad(GamepadButtonType::DPadLeft) {
+        stepped.send(Stepped(focus.index, -1));
+    } else if keys.just_pressed(KeyCode::Right) || pad(GamepadButtonType::DPadRight) {
+        stepped.send(Stepped(focus.index, 1));
+    } else if keys.just_pressed(KeyCode::Return) || pad(GamepadButtonType::South) {
+        activated.send(Activated(focus.index));
+    } else if keys.just_pressed(KeyCode::Escape) || pad(GamepadButtonType::East) {
+        back.send(Back);
+    }
+    for (button, interaction) in interactions.iter() {
+        match interaction {
+            Interaction::Hovered => focus.index = button.0,
+            Interaction::Clicked => {
+                focus.index = button.0;
+                activated.send(Activated(button.0));
+            }
+            Interaction::None => {}
+        }
+    }
+}
+
+// 画面が変わったら、選んでいるボタンを先頭に戻す（ボタンの数は画面を作るSystemが決める）
+pub fn reset_focus(screen: Res<Screen>, mut focus: ResMut<Focus>) {
+    if screen.is_changed() {
+        *focus = Focus::default();
+    }
+}
+
+// examples/ui/button.rsと同じ色分けで、選んでいるボタンは乗せたときの色にする
+pub fn button_colors(
+    focus: Res<Focus>,
+    mut buttons: Query<(&WidgetButton, &Interaction, &mut UiColor)>,
+) {
+    for (button, interaction, mut color) in buttons.iter_mut() {
+        *color = match interaction {
+            Interaction::Clicked => PRESSED_BUTTON,
+            _ if button.0 == focus.index => HOVERED_BUTTON,
+            Interaction::Hovered => HOVERED_BUTTON,
+            Interaction::None => NORMAL_BUTTON,
+        }
+        .into();
+    }
+}
+
+// ホイールでスクロールし、選んでいるボタンがリストの枠からはみ出ないようにする
+pub fn scroll_lists(
+    mut wheel_events: EventReader<MouseWheel>,
+    focus: Res<Focus>,
+    mut lists: Query<(&mut ScrollingList, &mut Style, &Children, &Parent)>,
+    nodes: Query<&Node>,
+    items: Query<&WidgetButton>,
+) {
+    let wheel: f32 = wheel_events
+        .iter()
+        .map(|event| match event.unit {
+            MouseScrollUnit::Line => event.y * SCROLL_LINE,
+            MouseScrollUnit::Pixel => event.y,
+        })
+        .sum();
+    for (mut list, mut style, children, parent) in lists.iter_mut() {
+        let heights: Vec<f32> = children
+            .iter()
+            .map(|child| {
+                nodes
+                    .get(*child)
+                    .map_or(0.0, |node| node.size.y + 2.0 * ITEM_MARGIN)
+            })
+            .collect();
+        let panel_height = nodes.get(parent.0).map_or(0.0, |node| node.size.y);
+        let max_scroll = (heights.iter().sum::<f32>() - panel_height).max(0.0);
+        let mut position = list.position + wheel;
+        if focus.is_changed() {
+            if let Some(i) = children.iter().position(
+                |child| matches!(items.get(*child), Ok(button) if button.0 == focus.index),
+            ) {
+                // 選んでいるボタンの上端と下端（リストの上からの距離）
+                let top: f32 = heights[..i].iter().sum();
+                let bottom = top + heights[i];
+                position = position.max(-top).min(panel_height - bottom);
+            }
+        }
+        list.position = position.clamp(-max_scroll, 0.0);
+        style.position.top = Val::Px(list.position);
+    }
+}
+
+// ボタンの文字を、ボタンの番号の順に並べたlabelsに書き換える
+pub fn set_button_labels(
+    labels: &[String],
+    buttons: &Query<(&WidgetButton, &Children)>,
+    texts: &mut Query<&mut Text>,
+) {
+    for (button, children) in buttons.iter() {
+        let label = match labels.get(button.0) {
+            Some(label) => label,
+            None => continue,
+        };
+        for child in children.iter() {
+            if let Ok(mut text) = texts.get_mut(*child) {
+                if text.sections[0].value != *label {
+                    text.sections[0].value = label.clone();
+                }
+            }
+        }
+    }
+}
+
+// ウィジェットのイベントとリソースとSystemを登録する
+pub fn add_widgets(app: &mut App) -> &mut App {
+    app.insert_resource(Focus::default())
+        .add_event::<Activated>()
+        .add_event::<Back>()
+        .add_event::<Stepped>()
+        .add_system(navigate)
+        .add_system(button_colors.after(navigate))
+        .add_system(scroll_lists.after(navigate))
+}